@@ -0,0 +1,266 @@
+mod plateau;
+
+pub use plateau::Plateau;
+
+use crate::data::models::{AnalysisObjective, AnalysisParameters, DataPoint};
+use crate::error::AppError;
+use crate::heatmap::data_reader::read_csv_data;
+use crate::utils::wg_quick::patch_config_file;
+use std::cmp::Ordering;
+use std::fmt;
+use std::path::Path;
+
+// Направление теста скорости
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Upload,
+    Download,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Direction::Upload => write!(f, "upload"),
+            Direction::Download => write!(f, "download"),
+        }
+    }
+}
+
+// Оценка одной пары MTU по выбранному критерию
+#[derive(Debug, Clone, Copy)]
+pub struct PairScore {
+    pub server_mtu: u16,
+    pub peer_mtu: u16,
+    pub upload_mbps: f64,
+    pub download_mbps: f64,
+    pub score: f64,
+}
+
+impl PairScore {
+    // Функция для расчета, на сколько процентов пара уступает другой
+    pub fn percent_below(&self, best: &PairScore) -> f64 {
+        if best.score > 0.0 {
+            (best.score - self.score) / best.score * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+// Рекомендация по результатам перебора
+#[derive(Debug, Clone)]
+pub struct Recommendation {
+    // Лучшая измеренная пара
+    pub best: PairScore,
+    // Устойчивый к шуму пик оценки, от которого отсчитывается допуск
+    pub peak: f64,
+    // Оценка, не ниже которой пара входит в плато
+    pub threshold: f64,
+    // Остальные пары не ниже порога, по убыванию оценки
+    pub runner_up: Vec<PairScore>,
+    // Самое широкое плато, рекомендуемая пара — в его глубине
+    pub plateau: Plateau,
+}
+
+// Функция для анализа CSV файла и вывода рекомендуемой пары MTU
+pub fn analyze(params: AnalysisParameters) -> Result<(), AppError> {
+    println!("Analyzing results from {}", params.log_filepath);
+
+    let data = read_csv_data(&params.log_filepath).map_err(|e| {
+        AppError::Analysis(format!("Failed to read {}: {}", params.log_filepath, e))
+    })?;
+    let scores = score_pairs(&data, params.objective, params.upload_weight);
+    let recommendation = recommend(&scores, params.tolerance_percent)
+        .ok_or_else(|| AppError::Analysis("No valid data found in CSV file".to_string()))?;
+
+    println!(
+        "Scored {} MTU pairs by {}",
+        scores.len(),
+        describe_objective(&data, &params)
+    );
+    print_recommendation(&recommendation, params.tolerance_percent);
+
+    // Рекомендуемый MTU в конфигурации wg-quick каждой из сторон
+    let pick = &recommendation.plateau.pick;
+    let sides = [
+        ("server", &params.server_config, pick.server_mtu),
+        ("peer", &params.peer_config, pick.peer_mtu),
+    ];
+    for (side, config, mtu) in sides {
+        if let Some(config) = config {
+            let output_dir = params
+                .config_output_dir
+                .as_ref()
+                .map(|dir| Path::new(dir).join(side));
+            patch_config_file(config, u32::from(mtu), output_dir.as_deref())?;
+        }
+    }
+    Ok(())
+}
+
+// Функция для оценки каждой пары MTU. Скорость направления — принятая
+// получателем, она не включает повторно отправленные и потерянные данные.
+pub fn score_pairs(
+    data: &[DataPoint],
+    objective: AnalysisObjective,
+    upload_weight: f64,
+) -> Vec<PairScore> {
+    let bottleneck = bottleneck_direction(data);
+
+    data.iter()
+        .map(|point| {
+            let upload = point.upload_rcv_mbps;
+            let download = point.download_rcv_mbps;
+            let score = match objective {
+                AnalysisObjective::Min => upload.min(download),
+                AnalysisObjective::Weighted => {
+                    upload * upload_weight + download * (1.0 - upload_weight)
+                }
+                AnalysisObjective::Bottleneck => match bottleneck {
+                    Direction::Upload => upload,
+                    Direction::Download => download,
+                },
+            };
+
+            PairScore {
+                server_mtu: point.server_mtu,
+                peer_mtu: point.peer_mtu,
+                upload_mbps: upload,
+                download_mbps: download,
+                score,
+            }
+        })
+        .collect()
+}
+
+// Функция для определения направления, более медленного в среднем по всему перебору
+pub fn bottleneck_direction(data: &[DataPoint]) -> Direction {
+    let upload: f64 = data.iter().map(|point| point.upload_rcv_mbps).sum();
+    let download: f64 = data.iter().map(|point| point.download_rcv_mbps).sum();
+
+    if upload <= download {
+        Direction::Upload
+    } else {
+        Direction::Download
+    }
+}
+
+// Функция для выбора пары по оценкам. Одиночный максимум часто оказывается
+// выбросом рядом с обрывом, поэтому рекомендуется пара в глубине самого
+// широкого плато из пар, уступающих сглаженному пику не больше чем на
+// tolerance_percent процентов. None, если оценок нет.
+pub fn recommend(scores: &[PairScore], tolerance_percent: f64) -> Option<Recommendation> {
+    let mut ranked = scores.to_vec();
+    ranked.sort_by(compare_scores);
+
+    let mut ranked = ranked.into_iter();
+    let best = ranked.next()?;
+    let peak = plateau::smoothed_peak(scores)?;
+    let threshold = peak * (1.0 - tolerance_percent / 100.0);
+    let runner_up = ranked.take_while(|pair| pair.score >= threshold).collect();
+    let plateau = plateau::widest_plateau(scores, threshold)?;
+
+    Some(Recommendation {
+        best,
+        peak,
+        threshold,
+        runner_up,
+        plateau,
+    })
+}
+
+// Порядок пар: сначала лучшая оценка, при равенстве большие MTU —
+// тот же объем данных передается меньшим числом пакетов
+fn compare_scores(a: &PairScore, b: &PairScore) -> Ordering {
+    b.score
+        .total_cmp(&a.score)
+        .then(b.server_mtu.cmp(&a.server_mtu))
+        .then(b.peer_mtu.cmp(&a.peer_mtu))
+}
+
+// Функция для описания критерия оценки
+fn describe_objective(data: &[DataPoint], params: &AnalysisParameters) -> String {
+    match params.objective {
+        AnalysisObjective::Min => "the slower of upload and download".to_string(),
+        AnalysisObjective::Weighted => format!(
+            "{:.0}% upload + {:.0}% download",
+            params.upload_weight * 100.0,
+            (1.0 - params.upload_weight) * 100.0
+        ),
+        AnalysisObjective::Bottleneck => format!(
+            "{} throughput, the slower direction across the sweep",
+            bottleneck_direction(data)
+        ),
+    }
+}
+
+// Функция для вывода рекомендации
+fn print_recommendation(recommendation: &Recommendation, tolerance_percent: f64) {
+    let best = &recommendation.best;
+    println!(
+        "Best measured: server MTU {}, peer MTU {}, score {:.2} Mbps (upload {:.2} Mbps, download {:.2} Mbps)",
+        best.server_mtu, best.peer_mtu, best.score, best.upload_mbps, best.download_mbps
+    );
+
+    let nearby = &recommendation.runner_up;
+    match nearby.first() {
+        Some(runner_up) => println!(
+            "Runner-up: server MTU {}, peer MTU {}, score {:.2} Mbps, {:.1}% below the best (server {:+}, peer {:+}), {} pairs above the plateau threshold",
+            runner_up.server_mtu,
+            runner_up.peer_mtu,
+            runner_up.score,
+            runner_up.percent_below(best),
+            i32::from(runner_up.server_mtu) - i32::from(best.server_mtu),
+            i32::from(runner_up.peer_mtu) - i32::from(best.peer_mtu),
+            nearby.len()
+        ),
+        None => println!("No other pair above the plateau threshold, the best pair is an isolated peak"),
+    }
+    println!(
+        "Plateau threshold: {:.2} Mbps, {}% below the peak of {:.2} Mbps averaged over 3x3 neighbouring pairs",
+        recommendation.threshold, tolerance_percent, recommendation.peak
+    );
+
+    let plateau = &recommendation.plateau;
+    let range = |mtus: Vec<u16>| {
+        let min = mtus.iter().min().copied().unwrap_or_default();
+        let max = mtus.iter().max().copied().unwrap_or_default();
+        format!("{}-{}", min, max)
+    };
+    println!(
+        "Widest plateau: {} pairs, server MTU {}, peer MTU {}",
+        plateau.cells.len(),
+        range(plateau.cells.iter().map(|pair| pair.server_mtu).collect()),
+        range(plateau.cells.iter().map(|pair| pair.peer_mtu).collect())
+    );
+
+    let pick = &plateau.pick;
+    println!(
+        "Recommended: server MTU {}, peer MTU {}, score {:.2} Mbps (upload {:.2} Mbps, download {:.2} Mbps)",
+        pick.server_mtu, pick.peer_mtu, pick.score, pick.upload_mbps, pick.download_mbps
+    );
+    println!(
+        "  {:.1}% below the best measured pair and {} bytes of MTU away from it, depth {} inside the plateau (1 is its edge)",
+        pick.percent_below(best),
+        mtu_distance(pick, best),
+        plateau.depth
+    );
+    match &plateau.nearest_collapse {
+        Some(collapse) => println!(
+            "  nearest collapse: server MTU {}, peer MTU {}, score {:.2} Mbps, {:.1}% below the best, {} bytes of MTU away",
+            collapse.server_mtu,
+            collapse.peer_mtu,
+            collapse.score,
+            collapse.percent_below(best),
+            mtu_distance(collapse, pick)
+        ),
+        None => println!("  no pair in the sweep falls below the plateau"),
+    }
+}
+
+// Функция для расчета расстояния между парами: наибольшее отличие MTU одной из сторон
+fn mtu_distance(a: &PairScore, b: &PairScore) -> u16 {
+    a.server_mtu
+        .abs_diff(b.server_mtu)
+        .max(a.peer_mtu.abs_diff(b.peer_mtu))
+}
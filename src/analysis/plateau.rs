@@ -0,0 +1,345 @@
+use super::{PairScore, compare_scores, mtu_distance};
+use std::collections::VecDeque;
+
+// Связная область сетки перебора, где оценка не ниже порога
+#[derive(Debug, Clone)]
+pub struct Plateau {
+    pub cells: Vec<PairScore>,
+    // Пара в глубине плато, наиболее удаленная от его края
+    pub pick: PairScore,
+    // Сколько шагов сетки от выбранной пары до края плато, 1 — пара на краю
+    pub depth: usize,
+    // Ближайшая к выбранной паре пара с оценкой ниже порога
+    pub nearest_collapse: Option<PairScore>,
+}
+
+// Сетка перебора: строки — MTU сервера, столбцы — MTU пира.
+// Пропущенные пары (адаптивный или двоичный перебор) остаются пустыми.
+struct Grid {
+    server_mtus: Vec<u16>,
+    peer_mtus: Vec<u16>,
+    cells: Vec<Option<PairScore>>,
+}
+
+impl Grid {
+    fn new(scores: &[PairScore]) -> Self {
+        let axis = |mtu: fn(&PairScore) -> u16| {
+            let mut mtus: Vec<u16> = scores.iter().map(mtu).collect();
+            mtus.sort_unstable();
+            mtus.dedup();
+            mtus
+        };
+        let server_mtus = axis(|pair| pair.server_mtu);
+        let peer_mtus = axis(|pair| pair.peer_mtu);
+
+        let mut cells: Vec<Option<PairScore>> = vec![None; server_mtus.len() * peer_mtus.len()];
+        for pair in scores {
+            let row = server_mtus.binary_search(&pair.server_mtu).unwrap_or_default();
+            let col = peer_mtus.binary_search(&pair.peer_mtu).unwrap_or_default();
+            let cell = &mut cells[row * peer_mtus.len() + col];
+            // Повторный замер той же пары: оставляем лучший
+            if cell.is_none_or(|existing| compare_scores(pair, &existing).is_lt()) {
+                *cell = Some(*pair);
+            }
+        }
+
+        Grid {
+            server_mtus,
+            peer_mtus,
+            cells,
+        }
+    }
+
+    // Функция для получения соседей ячейки по строке и столбцу
+    fn neighbours(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        let cols = self.peer_mtus.len();
+        let (row, col) = (index / cols, index % cols);
+        let up = (row > 0).then(|| index - cols);
+        let down = (row + 1 < self.server_mtus.len()).then(|| index + cols);
+        let left = (col > 0).then(|| index - 1);
+        let right = (col + 1 < cols).then(|| index + 1);
+        [up, down, left, right].into_iter().flatten()
+    }
+
+    // Функция для получения ячеек окна 3×3 вокруг ячейки, включая ее саму
+    fn window(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        let cols = self.peer_mtus.len();
+        let (row, col) = (index / cols, index % cols);
+        let rows = row.saturating_sub(1)..(row + 2).min(self.server_mtus.len());
+        rows.flat_map(move |row| {
+            (col.saturating_sub(1)..(col + 2).min(cols)).map(move |col| row * cols + col)
+        })
+    }
+
+    // Функция для проверки, лежит ли ячейка на краю сетки
+    fn on_edge(&self, index: usize) -> bool {
+        self.neighbours(index).count() < 4
+    }
+}
+
+// Функция для оценки пика, устойчивого к шуму: наибольшее среднее по окну
+// 3×3 измеренных пар. Одиночный выброс усредняется с соседями, а окно у
+// обрыва захватывает упавшие пары, поэтому пик берется из глубины плато.
+pub(super) fn smoothed_peak(scores: &[PairScore]) -> Option<f64> {
+    let grid = Grid::new(scores);
+    (0..grid.cells.len())
+        .filter(|&index| grid.cells[index].is_some())
+        .map(|index| {
+            let window: Vec<f64> = grid
+                .window(index)
+                .filter_map(|cell| grid.cells[cell].map(|pair| pair.score))
+                .collect();
+            window.iter().sum::<f64>() / window.len() as f64
+        })
+        .max_by(f64::total_cmp)
+}
+
+// Функция для поиска самого широкого плато с оценкой не ниже threshold
+// и выбора пары в его глубине. Край сетки тоже считается краем плато:
+// что за пределами перебора, неизвестно.
+pub(super) fn widest_plateau(scores: &[PairScore], threshold: f64) -> Option<Plateau> {
+    let grid = Grid::new(scores);
+    let inside: Vec<Option<PairScore>> = grid
+        .cells
+        .iter()
+        .map(|cell| cell.filter(|pair| pair.score >= threshold))
+        .collect();
+
+    // Связные области, самая большая; при равенстве — с лучшей парой
+    let mut visited = vec![false; grid.cells.len()];
+    let mut widest: Option<Vec<(usize, PairScore)>> = None;
+    for start in 0..grid.cells.len() {
+        if inside[start].is_none() || visited[start] {
+            continue;
+        }
+
+        let mut region = Vec::new();
+        let mut queue = VecDeque::from([start]);
+        visited[start] = true;
+        while let Some(index) = queue.pop_front() {
+            region.extend(inside[index].map(|pair| (index, pair)));
+            for next in grid.neighbours(index) {
+                if inside[next].is_some() && !visited[next] {
+                    visited[next] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        let better = match &widest {
+            None => true,
+            Some(current) => {
+                region.len() > current.len()
+                    || (region.len() == current.len()
+                        && best_in(&region)
+                            .zip(best_in(current))
+                            .is_some_and(|(new, old)| compare_scores(&new, &old).is_lt()))
+            }
+        };
+        if better {
+            widest = Some(region);
+        }
+    }
+    let region = widest?;
+
+    // Глубина каждой ячейки плато: расстояние в шагах сетки до ближайшей
+    // ячейки вне плато или до выхода за край сетки
+    let mut in_region = vec![false; grid.cells.len()];
+    for &(index, _) in &region {
+        in_region[index] = true;
+    }
+    let mut depth = vec![usize::MAX; grid.cells.len()];
+    let mut queue = VecDeque::new();
+    for index in 0..grid.cells.len() {
+        if !in_region[index] {
+            depth[index] = 0;
+            queue.push_back(index);
+        }
+    }
+    for &(index, _) in &region {
+        if grid.on_edge(index) && depth[index] > 1 {
+            depth[index] = 1;
+            queue.push_back(index);
+        }
+    }
+    while let Some(index) = queue.pop_front() {
+        for next in grid.neighbours(index) {
+            if depth[index] + 1 < depth[next] {
+                depth[next] = depth[index] + 1;
+                queue.push_back(next);
+            }
+        }
+    }
+
+    // Самая глубокая пара; при равенстве — с лучшей оценкой
+    let (pick_index, pick) = region.iter().copied().min_by(|(a, a_pair), (b, b_pair)| {
+        depth[*b]
+            .cmp(&depth[*a])
+            .then_with(|| compare_scores(a_pair, b_pair))
+    })?;
+
+    let nearest_collapse = scores
+        .iter()
+        .filter(|pair| pair.score < threshold)
+        .min_by(|a, b| {
+            mtu_distance(a, &pick)
+                .cmp(&mtu_distance(b, &pick))
+                .then(a.score.total_cmp(&b.score))
+        })
+        .copied();
+
+    let mut cells: Vec<PairScore> = region.iter().map(|&(_, pair)| pair).collect();
+    cells.sort_by(compare_scores);
+
+    Some(Plateau {
+        cells,
+        pick,
+        depth: depth[pick_index],
+        nearest_collapse,
+    })
+}
+
+// Функция для получения лучшей пары области
+fn best_in(region: &[(usize, PairScore)]) -> Option<PairScore> {
+    region.iter().map(|&(_, pair)| pair).min_by(compare_scores)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::models::DEFAULT_PLATEAU_TOLERANCE_PERCENT;
+
+    const THRESHOLD: f64 = 50.0;
+
+    // Builds scores from rows of cells: row i is server MTU 1400 + 10 * i,
+    // column j is peer MTU 1400 + 10 * j. '#' is above the threshold, '@' is
+    // above it and better than '#', '.' is below it and ' ' was not measured.
+    fn scores(rows: &[&str]) -> Vec<PairScore> {
+        let mut scores = Vec::new();
+        for (row, cells) in rows.iter().enumerate() {
+            for (col, cell) in cells.chars().enumerate() {
+                let score = match cell {
+                    '#' => 100.0,
+                    '@' => 120.0,
+                    '.' => 10.0,
+                    _ => continue,
+                };
+                scores.push(pair(row, col, score));
+            }
+        }
+        scores
+    }
+
+    // Same layout as scores, with every cell measured
+    fn measured(rows: &[&[f64]]) -> Vec<PairScore> {
+        let mut scores = Vec::new();
+        for (row, cells) in rows.iter().enumerate() {
+            for (col, &score) in cells.iter().enumerate() {
+                scores.push(pair(row, col, score));
+            }
+        }
+        scores
+    }
+
+    fn pair(row: usize, col: usize, score: f64) -> PairScore {
+        PairScore {
+            server_mtu: 1400 + 10 * row as u16,
+            peer_mtu: 1400 + 10 * col as u16,
+            upload_mbps: score,
+            download_mbps: score,
+            score,
+        }
+    }
+
+    fn mtus(pair: &PairScore) -> (u16, u16) {
+        (pair.server_mtu, pair.peer_mtu)
+    }
+
+    #[test]
+    fn picks_the_centre_of_a_single_plateau() {
+        let scores = scores(&[".....", ".###.", ".###.", ".###.", "....."]);
+        let plateau = widest_plateau(&scores, THRESHOLD).unwrap();
+
+        assert_eq!(plateau.cells.len(), 9);
+        assert_eq!(mtus(&plateau.pick), (1420, 1420));
+        assert_eq!(plateau.depth, 2);
+        let collapse = plateau.nearest_collapse.unwrap();
+        assert_eq!(mtu_distance(&collapse, &plateau.pick), 20);
+    }
+
+    #[test]
+    fn equal_plateaus_are_decided_by_the_best_pair() {
+        let scores = scores(&["##.#@", "##.##"]);
+        let plateau = widest_plateau(&scores, THRESHOLD).unwrap();
+
+        assert_eq!(plateau.cells.len(), 4);
+        assert!(plateau.cells.iter().all(|pair| pair.peer_mtu >= 1430));
+        assert_eq!(mtus(&plateau.pick), (1400, 1440));
+        assert_eq!(plateau.depth, 1);
+    }
+
+    #[test]
+    fn a_wider_plateau_beats_a_better_pair() {
+        let scores = scores(&["###.@", "###.."]);
+        let plateau = widest_plateau(&scores, THRESHOLD).unwrap();
+
+        assert_eq!(plateau.cells.len(), 6);
+        assert!(plateau.cells.iter().all(|pair| pair.peer_mtu <= 1420));
+    }
+
+    #[test]
+    fn holes_bound_the_plateau_but_are_not_collapses() {
+        // Without the hole the centre would be the pick at depth 3
+        let scores = scores(&["#####", "#####", "## ##", "#####", "#####"]);
+        let plateau = widest_plateau(&scores, THRESHOLD).unwrap();
+
+        assert_eq!(plateau.cells.len(), 24);
+        assert!(plateau.cells.iter().all(|pair| mtus(pair) != (1420, 1420)));
+        assert_eq!(plateau.depth, 2);
+        // Equal depth and score: the highest MTUs win
+        assert_eq!(mtus(&plateau.pick), (1430, 1430));
+        assert!(plateau.nearest_collapse.is_none());
+    }
+
+    #[test]
+    fn a_noisy_outlier_beside_a_cliff_does_not_set_the_threshold() {
+        // A plateau around 100 with 2% noise, a cliff in the last column and an
+        // outlier of 130 right next to it
+        let scores = measured(&[
+            &[98.0, 101.0, 98.0, 102.0, 99.0, 40.0],
+            &[101.0, 98.0, 100.0, 98.0, 102.0, 40.0],
+            &[99.0, 102.0, 98.0, 101.0, 130.0, 40.0],
+            &[98.0, 100.0, 102.0, 99.0, 98.0, 40.0],
+            &[100.0, 98.0, 101.0, 98.0, 100.0, 40.0],
+        ]);
+
+        // Anchored to the best cell, the plateau is the outlier alone
+        let tolerance = 1.0 - DEFAULT_PLATEAU_TOLERANCE_PERCENT / 100.0;
+        let plateau = widest_plateau(&scores, 130.0 * tolerance).unwrap();
+        assert_eq!(mtus(&plateau.pick), (1420, 1440));
+
+        let peak = smoothed_peak(&scores).unwrap();
+        assert!(peak > 100.0 && peak < 110.0, "{}", peak);
+        let plateau = widest_plateau(&scores, peak * tolerance).unwrap();
+        assert_eq!(plateau.cells.len(), 25);
+        assert_eq!(mtus(&plateau.pick), (1420, 1420));
+        assert_eq!(plateau.depth, 3);
+    }
+
+    #[test]
+    fn smoothed_peak_of_a_single_cell_is_its_score() {
+        assert_eq!(smoothed_peak(&measured(&[&[42.0]])), Some(42.0));
+        assert_eq!(smoothed_peak(&[]), None);
+    }
+
+    #[test]
+    fn a_single_cell_is_a_plateau_on_the_edge() {
+        let plateau = widest_plateau(&scores(&["#"]), THRESHOLD).unwrap();
+        assert_eq!(plateau.cells.len(), 1);
+        assert_eq!(mtus(&plateau.pick), (1400, 1400));
+        assert_eq!(plateau.depth, 1);
+        assert!(plateau.nearest_collapse.is_none());
+
+        assert!(widest_plateau(&scores(&["."]), THRESHOLD).is_none());
+    }
+}
@@ -1,8 +1,17 @@
-use crate::data::models::{
-    DEFAULT_CONTROL_PORT, DEFAULT_IPERF_PORT, DEFAULT_MAX_MTU, DEFAULT_MIN_MTU, DEFAULT_STEP,
+use wg_mtu_finder::data::models::{
+    AnalysisObjective, DEFAULT_CONTROL_PORT, DEFAULT_CONTROL_TIMEOUT_SECS,
+    DEFAULT_HEARTBEAT_INTERVAL_SECS, DEFAULT_IPERF_PORT, DEFAULT_LAB_UNDERLAY_MTU,
+    DEFAULT_MAX_MTU, DEFAULT_MIN_MTU, DEFAULT_OMIT_SECS, DEFAULT_PARALLEL_STREAMS,
+    DEFAULT_PLATEAU_TOLERANCE_PERCENT, DEFAULT_REFINE_STEP, DEFAULT_REPEATS,
+    DEFAULT_SIM_BANDWIDTH_MBPS, DEFAULT_SIM_FRAGMENTATION_PENALTY, DEFAULT_SIM_NOISE,
+    DEFAULT_SIM_OVERHEAD, DEFAULT_SIM_SEED, DEFAULT_SIM_UNDERLAY_MTU, DEFAULT_STEP,
+    DEFAULT_TEST_DURATION_SECS, DEFAULT_UDP_BITRATE_MBPS, DEFAULT_UDP_PAYLOAD_SIZE,
+    DEFAULT_TOP_REGRESSIONS, DEFAULT_UPLOAD_WEIGHT, DEFAULT_WG_QUICK_OUTPUT_DIR, HeatmapDelta,
+    HeatmapMetric, HeatmapStatistic, SweepStrategy, TestProtocol, ThroughputEngine,
 };
 use chrono::Local;
 use clap::{Parser, Subcommand};
+use std::net::{IpAddr, Ipv4Addr};
 
 fn default_csv_filename() -> String {
     format!("wg_mtu_finder_{}.csv", Local::now().format("%Y%m%dT%H%M%S"))
@@ -12,12 +21,69 @@ fn default_heatmap_filename() -> String {
     format!("heatmap_{}.png", Local::now().format("%Y%m%dT%H%M%S"))
 }
 
+fn default_diff_heatmap_filename() -> String {
+    format!("heatmap_diff_{}.png", Local::now().format("%Y%m%dT%H%M%S"))
+}
+
+// Размер в байтах с необязательным суффиксом K, M или G, как у iperf3 -w
+fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let (digits, multiplier) = match value.chars().last().map(|c| c.to_ascii_uppercase()) {
+        Some('K') => (&value[..value.len() - 1], 1024),
+        Some('M') => (&value[..value.len() - 1], 1024 * 1024),
+        Some('G') => (&value[..value.len() - 1], 1024 * 1024 * 1024),
+        _ => (value, 1),
+    };
+
+    match digits.parse::<u64>() {
+        Ok(size) if size > 0 => Ok(size * multiplier),
+        _ => Err(format!("invalid size '{}', expected e.g. 262144, 256K or 4M", value)),
+    }
+}
+
+// Доля от 0 до 1, например штраф за фрагментацию
+fn parse_fraction(value: &str) -> Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(fraction),
+        _ => Err(format!("invalid value '{}', expected a number from 0 to 1", value)),
+    }
+}
+
+// Процент от 0 до 100, например потери пакетов
+fn parse_percent(value: &str) -> Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(percent) if (0.0..=100.0).contains(&percent) => Ok(percent),
+        _ => Err(format!("invalid value '{}', expected a percentage from 0 to 100", value)),
+    }
+}
+
+// Неотрицательное число, например относительный шум или скорость
+fn parse_non_negative(value: &str) -> Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(number) if number >= 0.0 && number.is_finite() => Ok(number),
+        _ => Err(format!("invalid value '{}', expected a non-negative number", value)),
+    }
+}
+
 #[derive(Parser)]
 #[command(
     name = "WireGuard MTU Finder",
     version = "1.0",
     author = "MrSomFeRGO",
-    about = "Utility to find optimal MTU for WireGuard connections"
+    about = "Utility to find optimal MTU for WireGuard connections",
+    after_help = "Exit codes:\n  \
+        0   success\n  \
+        2   invalid configuration or parameter mismatch\n  \
+        3   failed to read or set MTU\n  \
+        4   throughput engine unavailable or failed to start\n  \
+        5   control connection failed or timed out\n  \
+        6   unexpected or malformed control message\n  \
+        7   the other side reported an error\n  \
+        8   the other side aborted the sweep\n  \
+        9   failed to write results\n  \
+        10  failed to generate heatmap\n  \
+        11  failed to set up or run the lab\n  \
+        12  failed to analyze results"
 )]
 pub struct Cli {
     #[command(subcommand)]
@@ -41,34 +107,257 @@ pub enum Commands {
         max_mtu: u32,
 
         /// MTU step size
-        #[arg(long, value_name = "STEP", default_value_t = DEFAULT_STEP)]
+        #[arg(
+            long,
+            value_name = "STEP",
+            default_value_t = DEFAULT_STEP,
+            value_parser = clap::value_parser!(u32).range(1..)
+        )]
         step: u32,
 
         /// Control connection port
         #[arg(long, value_name = "PORT", default_value_t = DEFAULT_CONTROL_PORT)]
         server_port: u16,
 
+        /// Address to listen on for the control connection, e.g. the underlay endpoint
+        #[arg(long, value_name = "IP", default_value_t = IpAddr::V4(Ipv4Addr::UNSPECIFIED))]
+        bind: IpAddr,
+
+        /// Server address inside the tunnel, announced to the peer for throughput tests
+        #[arg(long, value_name = "IP")]
+        tunnel_ip: Option<String>,
+
+        /// Seconds without any message from the other side before giving up
+        #[arg(long, value_name = "SECS", default_value_t = DEFAULT_CONTROL_TIMEOUT_SECS)]
+        control_timeout: u64,
+
+        /// Seconds between heartbeat messages on the control connection
+        #[arg(long, value_name = "SECS", default_value_t = DEFAULT_HEARTBEAT_INTERVAL_SECS)]
+        heartbeat_interval: u64,
+
         /// Port for iperf tests
         #[arg(long, value_name = "PORT", default_value_t = DEFAULT_IPERF_PORT)]
         iperf_port: u16,
+
+        /// Throughput measurement engine
+        #[arg(long, value_enum, value_name = "ENGINE", default_value_t = ThroughputEngine::Iperf3)]
+        engine: ThroughputEngine,
+
+        /// MTU sweep strategy
+        #[arg(long, value_enum, value_name = "STRATEGY", default_value_t = SweepStrategy::Grid)]
+        strategy: SweepStrategy,
+
+        /// MTU step size for the refinement pass of the adaptive strategy
+        #[arg(
+            long,
+            value_name = "STEP",
+            default_value_t = DEFAULT_REFINE_STEP,
+            value_parser = clap::value_parser!(u32).range(1..=4)
+        )]
+        refine_step: u32,
     },
     /// Run in peer mode
     Peer {
         /// WireGuard interface name
-        #[arg(short, long, value_name = "INTERFACE")]
-        interface: String,
+        #[arg(short, long, value_name = "INTERFACE", required_unless_present = "simulate")]
+        interface: Option<String>,
 
-        /// Server IP address
-        #[arg(long, value_name = "SERVER_IP")]
-        server_ip: String,
+        /// Server IP address inside the tunnel, target of throughput tests [default: announced by the server]
+        #[arg(
+            long,
+            value_name = "SERVER_IP",
+            required_unless_present_any = ["simulate", "control_ip"]
+        )]
+        server_ip: Option<String>,
+
+        /// Server address for the control connection [default: SERVER_IP]
+        #[arg(long, value_name = "IP")]
+        control_ip: Option<String>,
 
         /// Control connection port
         #[arg(long, value_name = "PORT", default_value_t = DEFAULT_CONTROL_PORT)]
         server_port: u16,
 
-        /// Port for iperf tests
-        #[arg(long, value_name = "PORT", default_value_t = DEFAULT_IPERF_PORT)]
-        iperf_port: u16,
+        /// Seconds without any message from the other side before giving up
+        #[arg(long, value_name = "SECS", default_value_t = DEFAULT_CONTROL_TIMEOUT_SECS)]
+        control_timeout: u64,
+
+        /// Seconds between heartbeat messages on the control connection
+        #[arg(long, value_name = "SECS", default_value_t = DEFAULT_HEARTBEAT_INTERVAL_SECS)]
+        heartbeat_interval: u64,
+
+        /// Port for iperf tests [default: announced by the server]
+        #[arg(long, value_name = "PORT")]
+        iperf_port: Option<u16>,
+
+        /// Minimum MTU to test [default: announced by the server]
+        #[arg(long, value_name = "MIN_MTU")]
+        min_mtu: Option<u32>,
+
+        /// Maximum MTU to test [default: announced by the server]
+        #[arg(long, value_name = "MAX_MTU")]
+        max_mtu: Option<u32>,
+
+        /// MTU step size [default: announced by the server]
+        #[arg(long, value_name = "STEP", value_parser = clap::value_parser!(u32).range(1..))]
+        step: Option<u32>,
+
+        /// Step of the refinement pass of the adaptive strategy [default: announced by the server]
+        #[arg(long, value_name = "STEP", value_parser = clap::value_parser!(u32).range(1..=4))]
+        refine_step: Option<u32>,
+
+        /// Number of throughput runs per MTU pair; the CSV stores their mean and spread
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = DEFAULT_REPEATS,
+            value_parser = clap::value_parser!(u32).range(1..)
+        )]
+        repeats: u32,
+
+        /// Path to CSV output file
+        #[arg(long, value_name = "FILE", default_value_t = default_csv_filename())]
+        csv_file: String,
+
+        /// Resume an interrupted sweep, skipping pairs already in this CSV file and appending to it
+        #[arg(long, value_name = "FILE", conflicts_with = "csv_file")]
+        resume: Option<String>,
+
+        /// After the sweep, set the recommended MTU pair on both ends and keep it
+        #[arg(long)]
+        apply_best: bool,
+
+        /// Throughput measurement engine [default: announced by the server]
+        #[arg(long, value_enum, value_name = "ENGINE")]
+        engine: Option<ThroughputEngine>,
+
+        /// MTU sweep strategy [default: announced by the server]
+        #[arg(long, value_enum, value_name = "STRATEGY")]
+        strategy: Option<SweepStrategy>,
+
+        /// Transport protocol for throughput tests
+        #[arg(long, value_enum, value_name = "PROTOCOL", default_value_t = TestProtocol::Tcp)]
+        protocol: TestProtocol,
+
+        /// Target bitrate for UDP tests in Mbit/s
+        #[arg(long, value_name = "MBPS", default_value_t = DEFAULT_UDP_BITRATE_MBPS)]
+        udp_bitrate: f64,
+
+        /// UDP datagram payload size in bytes, at most 65507 (IPv4 limit)
+        #[arg(
+            long,
+            value_name = "BYTES",
+            default_value_t = DEFAULT_UDP_PAYLOAD_SIZE,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..=65507)
+        )]
+        udp_payload_size: usize,
+
+        /// Duration of each throughput test in seconds
+        #[arg(
+            long,
+            value_name = "SECS",
+            default_value_t = DEFAULT_TEST_DURATION_SECS,
+            value_parser = clap::value_parser!(u64).range(1..)
+        )]
+        duration: u64,
+
+        /// Seconds at the start of each test excluded from the result (TCP slow start)
+        #[arg(long, value_name = "SECS", default_value_t = DEFAULT_OMIT_SECS)]
+        omit: u64,
+
+        /// Number of parallel streams per test
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = DEFAULT_PARALLEL_STREAMS,
+            value_parser = clap::value_parser!(u32).range(1..=128)
+        )]
+        parallel: u32,
+
+        /// Socket buffer (TCP window) size, e.g. 256K or 4M [default: system]
+        #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+        window: Option<u64>,
+
+        /// TCP congestion control algorithm, e.g. cubic or bbr [default: system]
+        #[arg(long, value_name = "ALGO")]
+        congestion: Option<String>,
+
+        /// Run the sweep against a synthetic network model instead of a real server and tunnel
+        #[arg(long, conflicts_with_all = ["control_ip", "engine"])]
+        simulate: bool,
+
+        /// Simulation: MTU of the network underneath the tunnel
+        #[arg(
+            long,
+            value_name = "MTU",
+            default_value_t = DEFAULT_SIM_UNDERLAY_MTU,
+            requires = "simulate",
+            value_parser = clap::value_parser!(u32).range(576..=65535)
+        )]
+        sim_underlay_mtu: u32,
+
+        /// Simulation: bytes added by encapsulation (80 for WireGuard over IPv6, 60 over IPv4)
+        #[arg(
+            long,
+            value_name = "BYTES",
+            default_value_t = DEFAULT_SIM_OVERHEAD,
+            requires = "simulate",
+            value_parser = clap::value_parser!(u32).range(0..=256)
+        )]
+        sim_overhead: u32,
+
+        /// Simulation: bandwidth of the underlay network in Mbit/s
+        #[arg(
+            long,
+            value_name = "MBPS",
+            default_value_t = DEFAULT_SIM_BANDWIDTH_MBPS,
+            requires = "simulate",
+            value_parser = parse_non_negative
+        )]
+        sim_bandwidth: f64,
+
+        /// Simulation: share of throughput lost when outer packets are fragmented (0 to 1)
+        #[arg(
+            long,
+            value_name = "FRACTION",
+            default_value_t = DEFAULT_SIM_FRAGMENTATION_PENALTY,
+            requires = "simulate",
+            value_parser = parse_fraction
+        )]
+        sim_fragmentation_penalty: f64,
+
+        /// Simulation: relative standard deviation of measurement noise
+        #[arg(
+            long,
+            value_name = "FRACTION",
+            default_value_t = DEFAULT_SIM_NOISE,
+            requires = "simulate",
+            value_parser = parse_non_negative
+        )]
+        sim_noise: f64,
+
+        /// Simulation: seed of the noise generator, the same seed reproduces a run
+        #[arg(long, value_name = "SEED", default_value_t = DEFAULT_SIM_SEED, requires = "simulate")]
+        sim_seed: u64,
+    },
+    /// Build a WireGuard tunnel between two network namespaces on this host and sweep it
+    Lab {
+        /// MTU of the veth pair underneath the tunnel
+        #[arg(
+            long,
+            value_name = "MTU",
+            default_value_t = DEFAULT_LAB_UNDERLAY_MTU,
+            value_parser = clap::value_parser!(u32).range(1280..=65535)
+        )]
+        underlay_mtu: u32,
+
+        /// One-way delay added by tc netem in each direction, in milliseconds
+        #[arg(long, value_name = "MS", value_parser = parse_non_negative)]
+        delay: Option<f64>,
+
+        /// Packet loss added by tc netem in each direction, in percent
+        #[arg(long, value_name = "PERCENT", value_parser = parse_percent)]
+        loss: Option<f64>,
 
         /// Minimum MTU to test
         #[arg(long, value_name = "MIN_MTU", default_value_t = DEFAULT_MIN_MTU)]
@@ -79,9 +368,44 @@ pub enum Commands {
         max_mtu: u32,
 
         /// MTU step size
-        #[arg(long, value_name = "STEP", default_value_t = DEFAULT_STEP)]
+        #[arg(
+            long,
+            value_name = "STEP",
+            default_value_t = DEFAULT_STEP,
+            value_parser = clap::value_parser!(u32).range(1..)
+        )]
         step: u32,
 
+        /// Throughput measurement engine
+        #[arg(long, value_enum, value_name = "ENGINE", default_value_t = ThroughputEngine::Native)]
+        engine: ThroughputEngine,
+
+        /// MTU sweep strategy
+        #[arg(long, value_enum, value_name = "STRATEGY", default_value_t = SweepStrategy::Grid)]
+        strategy: SweepStrategy,
+
+        /// Transport protocol for throughput tests
+        #[arg(long, value_enum, value_name = "PROTOCOL", default_value_t = TestProtocol::Tcp)]
+        protocol: TestProtocol,
+
+        /// Duration of each throughput test in seconds
+        #[arg(
+            long,
+            value_name = "SECS",
+            default_value_t = DEFAULT_TEST_DURATION_SECS,
+            value_parser = clap::value_parser!(u64).range(1..)
+        )]
+        duration: u64,
+
+        /// Number of throughput runs per MTU pair
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = DEFAULT_REPEATS,
+            value_parser = clap::value_parser!(u32).range(1..)
+        )]
+        repeats: u32,
+
         /// Path to CSV output file
         #[arg(long, value_name = "FILE", default_value_t = default_csv_filename())]
         csv_file: String,
@@ -95,5 +419,79 @@ pub enum Commands {
         /// The filepath where the heatmap PNG file will be saved
         #[arg(long, value_name = "FILE", default_value_t = default_heatmap_filename())]
         heatmap_filepath: String,
+
+        /// Metric to plot
+        #[arg(long, value_enum, value_name = "METRIC", default_value_t = HeatmapMetric::Bandwidth)]
+        metric: HeatmapMetric,
+
+        /// Statistic of repeated runs to plot
+        #[arg(long, value_enum, value_name = "STATISTIC", default_value_t = HeatmapStatistic::Mean)]
+        statistic: HeatmapStatistic,
+    },
+    /// Recommend the MTU pair to deploy from an existing log file
+    Analyze {
+        /// The filepath to the log file (CSV) to analyze
+        #[arg(long, value_name = "FILE")]
+        log_filepath: String,
+
+        /// How to score each MTU pair from its upload and download throughput
+        #[arg(long, value_enum, value_name = "OBJECTIVE", default_value_t = AnalysisObjective::Min)]
+        objective: AnalysisObjective,
+
+        /// Weight of upload throughput for the weighted objective, download gets the rest
+        #[arg(long, value_name = "WEIGHT", default_value_t = DEFAULT_UPLOAD_WEIGHT, value_parser = parse_fraction)]
+        upload_weight: f64,
+
+        /// How far below the peak score (the best average over 3x3 neighbouring pairs) a pair
+        /// may be to count towards the plateau, in percent
+        #[arg(
+            long,
+            value_name = "PERCENT",
+            default_value_t = DEFAULT_PLATEAU_TOLERANCE_PERCENT,
+            value_parser = parse_percent
+        )]
+        tolerance: f64,
+
+        /// wg-quick config of the server to set the recommended server MTU in
+        #[arg(long, value_name = "FILE")]
+        server_config: Option<String>,
+
+        /// wg-quick config of the peer to set the recommended peer MTU in
+        #[arg(long, value_name = "FILE")]
+        peer_config: Option<String>,
+
+        /// Directory for the patched configs, written to its server/ and peer/ subdirectories
+        #[arg(long, value_name = "DIR", default_value = DEFAULT_WG_QUICK_OUTPUT_DIR)]
+        config_dir: String,
+
+        /// Print the config changes as a diff instead of writing patched configs
+        #[arg(long, conflicts_with = "config_dir")]
+        config_diff: bool,
+    },
+    /// Compare two log files and generate a heatmap of the difference
+    Compare {
+        /// The log file (CSV) of the run to compare against
+        #[arg(long, value_name = "FILE")]
+        baseline: String,
+
+        /// The log file (CSV) of the run to compare
+        #[arg(long, value_name = "FILE")]
+        candidate: String,
+
+        /// The filepath where the difference heatmap PNG file will be saved
+        #[arg(long, value_name = "FILE", default_value_t = default_diff_heatmap_filename())]
+        heatmap_filepath: String,
+
+        /// Metric to plot and to list regressions for
+        #[arg(long, value_enum, value_name = "METRIC", default_value_t = HeatmapMetric::Bandwidth)]
+        metric: HeatmapMetric,
+
+        /// How to express the change of each cell
+        #[arg(long, value_enum, value_name = "DELTA", default_value_t = HeatmapDelta::Percent)]
+        delta: HeatmapDelta,
+
+        /// Number of largest regressions to list
+        #[arg(long, value_name = "N", default_value_t = DEFAULT_TOP_REGRESSIONS)]
+        top: usize,
     },
 }
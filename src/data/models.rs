@@ -1,13 +1,140 @@
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
 
 // Константы
 pub const DEFAULT_MIN_MTU: u32 = 1280;
 pub const DEFAULT_MAX_MTU: u32 = 1500;
 pub const DEFAULT_STEP: u32 = 20;
+pub const DEFAULT_REFINE_STEP: u32 = 2;
+pub const DEFAULT_REPEATS: u32 = 1;
 pub const DEFAULT_CONTROL_PORT: u16 = 9876;
 pub const DEFAULT_IPERF_PORT: u16 = 5201;
+pub const DEFAULT_CONTROL_TIMEOUT_SECS: u64 = 60;
+pub const DEFAULT_HEARTBEAT_INTERVAL_SECS: u64 = 5;
+pub const DEFAULT_UDP_BITRATE_MBPS: f64 = 100.0;
+pub const DEFAULT_UDP_PAYLOAD_SIZE: usize = 1400;
+pub const DEFAULT_TEST_DURATION_SECS: u64 = 5;
+pub const DEFAULT_OMIT_SECS: u64 = 0;
+pub const DEFAULT_PARALLEL_STREAMS: u32 = 1;
+pub const DEFAULT_SIM_UNDERLAY_MTU: u32 = 1500;
+pub const DEFAULT_SIM_OVERHEAD: u32 = 80;
+pub const DEFAULT_SIM_BANDWIDTH_MBPS: f64 = 1000.0;
+pub const DEFAULT_SIM_FRAGMENTATION_PENALTY: f64 = 0.6;
+pub const DEFAULT_SIM_NOISE: f64 = 0.03;
+pub const DEFAULT_SIM_SEED: u64 = 1;
+pub const DEFAULT_SIM_INTERFACE: &str = "sim-peer";
+pub const DEFAULT_SIM_SERVER_IP: &str = "simulated";
+pub const DEFAULT_LAB_UNDERLAY_MTU: u32 = 1500;
+pub const DEFAULT_UPLOAD_WEIGHT: f64 = 0.5;
+pub const DEFAULT_PLATEAU_TOLERANCE_PERCENT: f64 = 5.0;
+pub const DEFAULT_WG_QUICK_OUTPUT_DIR: &str = "wg-quick";
+pub const DEFAULT_TOP_REGRESSIONS: usize = 10;
 
-// Структуры для тестирования
+// Движок для замера пропускной способности
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ThroughputEngine {
+    /// Built-in TCP/UDP bulk transfer, no external tools required
+    Native,
+    /// External iperf3 binary
+    Iperf3,
+}
+
+// Стратегия перебора значений MTU
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SweepStrategy {
+    /// Test every server/peer MTU pair of the range
+    Grid,
+    /// Bisect for the largest MTU pair before throughput collapses
+    Binary,
+    /// Coarse grid, then a fine grid around the best pair
+    Adaptive,
+}
+
+// Транспортный протокол теста скорости
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TestProtocol {
+    /// TCP bulk transfer
+    Tcp,
+    /// UDP stream at a fixed bitrate, reports jitter and loss
+    Udp,
+}
+
+// Параметры одного теста скорости, общие для всех движков
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ThroughputOptions {
+    pub protocol: TestProtocol,
+    pub udp_bitrate_mbps: f64,
+    pub udp_payload_size: usize,
+    #[serde(default = "default_test_duration_secs")]
+    pub duration_secs: u64,
+    // Начальный интервал, не учитываемый в результате (разгон TCP)
+    #[serde(default)]
+    pub omit_secs: u64,
+    #[serde(default = "default_parallel_streams")]
+    pub parallel_streams: u32,
+    // Размер буферов сокета (окна TCP) в байтах
+    #[serde(default)]
+    pub window_bytes: Option<u64>,
+    // Алгоритм управления перегрузкой TCP, например cubic или bbr
+    #[serde(default)]
+    pub congestion: Option<String>,
+}
+
+fn default_test_duration_secs() -> u64 {
+    DEFAULT_TEST_DURATION_SECS
+}
+
+fn default_parallel_streams() -> u32 {
+    DEFAULT_PARALLEL_STREAMS
+}
+
+// Результат одного теста скорости в одном направлении
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThroughputStats {
+    pub rcv_mbps: f64,
+    pub send_mbps: f64,
+    pub jitter_ms: f64,
+    pub lost_packets: u64,
+    pub loss_percent: f64,
+    // Повторные передачи TCP на стороне отправителя
+    pub retransmits: u64,
+    // Загрузка CPU на стороне клиента (host) и сервера (remote)
+    pub host_cpu_percent: f64,
+    pub remote_cpu_percent: f64,
+}
+
+// Параметры синтетической модели сети для режима симуляции
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SimulationModel {
+    // MTU сети под туннелем
+    pub underlay_mtu: u32,
+    // Байты, добавляемые инкапсуляцией (80 для WireGuard поверх IPv6, 60 поверх IPv4)
+    pub overhead: u32,
+    // Пропускная способность сети под туннелем
+    pub bandwidth_mbps: f64,
+    // Доля скорости, теряемая при фрагментации внешних пакетов
+    pub fragmentation_penalty: f64,
+    // Относительное стандартное отклонение шума измерений
+    pub noise: f64,
+    // Начальное значение генератора шума, для воспроизводимых запусков
+    pub seed: u64,
+}
+
+// Статистика повторных замеров одной метрики
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct MetricSummary {
+    pub mean: f64,
+    pub median: f64,
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+    // Полуширина 95% доверительного интервала для среднего
+    pub ci95: f64,
+}
+
+// Структуры для тестирования.
+// Значения метрик — средние по повторам, разброс хранится в *_stats.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MtuTestResult {
     pub server_mtu: u32,
@@ -16,6 +143,33 @@ pub struct MtuTestResult {
     pub upload_send_mbps: f64,
     pub download_rcv_mbps: f64,
     pub download_send_mbps: f64,
+    pub upload_jitter_ms: f64,
+    pub upload_lost_packets: u64,
+    pub upload_loss_percent: f64,
+    pub download_jitter_ms: f64,
+    pub download_lost_packets: u64,
+    pub download_loss_percent: f64,
+    pub upload_retransmits: u64,
+    pub download_retransmits: u64,
+    pub upload_peer_cpu_percent: f64,
+    pub upload_server_cpu_percent: f64,
+    pub download_peer_cpu_percent: f64,
+    pub download_server_cpu_percent: f64,
+    pub repeats: u32,
+    pub upload_rcv_stats: MetricSummary,
+    pub upload_send_stats: MetricSummary,
+    pub download_rcv_stats: MetricSummary,
+    pub download_send_stats: MetricSummary,
+    pub upload_jitter_stats: MetricSummary,
+    pub upload_loss_stats: MetricSummary,
+    pub download_jitter_stats: MetricSummary,
+    pub download_loss_stats: MetricSummary,
+    pub upload_retransmits_stats: MetricSummary,
+    pub download_retransmits_stats: MetricSummary,
+    pub upload_peer_cpu_stats: MetricSummary,
+    pub upload_server_cpu_stats: MetricSummary,
+    pub download_peer_cpu_stats: MetricSummary,
+    pub download_server_cpu_stats: MetricSummary,
 }
 
 // Структура параметров тестирования
@@ -25,25 +179,208 @@ pub struct TestParameters {
     pub max_mtu: u32,
     pub step: u32,
     pub control_port: u16,
+    pub bind_ip: IpAddr,
+    pub tunnel_ip: Option<String>,
     pub iperf_port: u16,
+    pub engine: ThroughputEngine,
+    pub strategy: SweepStrategy,
+    pub control_timeout_secs: u64,
+    pub heartbeat_interval_secs: u64,
+    pub refine_step: u32,
 }
 
 // Структура параметров клиента
+#[derive(Clone)]
 pub struct PeerParameters {
     pub interface: String,
-    pub server_ip: String,
+    // Адрес сервера в туннеле для замеров скорости; None — адрес, объявленный
+    // сервером. После согласования run_peer_sweep подставляет выбранный адрес.
+    pub server_ip: Option<String>,
+    pub control_ip: Option<String>,
     pub control_port: u16,
+    pub control_timeout_secs: u64,
+    pub heartbeat_interval_secs: u64,
+    pub repeats: u32,
+    pub csv_file: String,
+    pub resume: bool,
+    // Оставить на обеих сторонах лучшую пару MTU после перебора
+    pub apply_best: bool,
+    pub requested: SweepRequest,
+    pub throughput: ThroughputOptions,
+    // Модель сети вместо реального сервера и туннеля
+    pub simulation: Option<SimulationModel>,
+}
+
+// Параметры перебора, которые сервер объявляет при подключении пира
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SweepConfig {
+    pub min_mtu: u32,
+    pub max_mtu: u32,
+    pub step: u32,
     pub iperf_port: u16,
+    pub engine: ThroughputEngine,
+    pub strategy: SweepStrategy,
+    // Шаг уточняющего прохода адаптивной стратегии
+    pub refine_step: u32,
+}
+
+// Адреса сервера, которые он объявляет при подключении пира
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ServerEndpoints {
+    // Адрес, на котором пир достучался до управляющего порта
+    pub control_ip: String,
+    // Адрес сервера внутри туннеля для замеров скорости
+    pub tunnel_ip: Option<String>,
+}
+
+// Параметры перебора, явно заданные на стороне пира.
+// Незаданные значения берутся у сервера, заданные должны с ним совпадать.
+#[derive(Debug, Clone, Default)]
+pub struct SweepRequest {
+    pub min_mtu: Option<u32>,
+    pub max_mtu: Option<u32>,
+    pub step: Option<u32>,
+    pub iperf_port: Option<u16>,
+    pub engine: Option<ThroughputEngine>,
+    pub strategy: Option<SweepStrategy>,
+    pub refine_step: Option<u32>,
+}
+
+// Метаданные запуска, сохраняются рядом с CSV файлом
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RunMetadata {
+    pub started_at: String,
+    pub interface: String,
+    pub server_ip: String,
+    #[serde(default)]
+    pub control_ip: Option<String>,
+    pub server_current_mtu: Option<u32>,
+    pub peer_current_mtu: Option<u32>,
     pub min_mtu: u32,
     pub max_mtu: u32,
     pub step: u32,
+    pub engine: ThroughputEngine,
+    pub strategy: SweepStrategy,
+    #[serde(default = "default_repeats")]
+    pub repeats: u32,
+    pub throughput: ThroughputOptions,
+    #[serde(default)]
+    pub simulation: Option<SimulationModel>,
+}
+
+fn default_repeats() -> u32 {
+    DEFAULT_REPEATS
+}
+
+// Параметры лабораторного стенда: туннель WireGuard между двумя сетевыми
+// пространствами имен на одном хосте
+pub struct LabParameters {
+    pub underlay_mtu: u32,
+    // Задержка и потери, добавляемые tc netem в каждом направлении
+    pub delay_ms: Option<f64>,
+    pub loss_percent: Option<f64>,
+    pub min_mtu: u32,
+    pub max_mtu: u32,
+    pub step: u32,
+    pub engine: ThroughputEngine,
+    pub strategy: SweepStrategy,
+    pub protocol: TestProtocol,
+    pub duration_secs: u64,
+    pub repeats: u32,
     pub csv_file: String,
 }
 
+// Параметры анализа результатов перебора
+pub struct AnalysisParameters {
+    pub log_filepath: String,
+    pub objective: AnalysisObjective,
+    // Вес направления к серверу для взвешенной суммы, загрузка получает остаток
+    pub upload_weight: f64,
+    // Насколько пара может уступать лучшей, чтобы попасть в плато, в процентах
+    pub tolerance_percent: f64,
+    // Конфигурации wg-quick сервера и пира, в которые записывается рекомендуемый MTU
+    pub server_config: Option<String>,
+    pub peer_config: Option<String>,
+    // Каталог для исправленных конфигураций; None — вывести изменения в виде diff
+    pub config_output_dir: Option<String>,
+}
+
+// Критерий оценки пары MTU при анализе
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AnalysisObjective {
+    /// Slower of the upload and download throughput
+    Min,
+    /// Weighted sum of upload and download throughput
+    Weighted,
+    /// Throughput in whichever direction is slower across the whole sweep
+    Bottleneck,
+}
+
 // Структура параметров хитмапы
 pub struct HeatmapParameters {
     pub log_filepath: String,
     pub heatmap_filepath: String,
+    pub metric: HeatmapMetric,
+    pub statistic: HeatmapStatistic,
+}
+
+// Структура параметров сравнения двух запусков
+pub struct CompareParameters {
+    pub baseline_filepath: String,
+    pub candidate_filepath: String,
+    pub heatmap_filepath: String,
+    pub metric: HeatmapMetric,
+    pub delta: HeatmapDelta,
+    // Сколько самых больших ухудшений вывести
+    pub top: usize,
+}
+
+// Разница между запусками, отображаемая на хитмапе сравнения
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HeatmapDelta {
+    /// Candidate minus baseline, in the units of the metric
+    Absolute,
+    /// Change relative to the baseline, in percent
+    Percent,
+}
+
+// Метрика, отображаемая на хитмапе
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HeatmapMetric {
+    /// Receive and send bandwidth in both directions
+    Bandwidth,
+    /// UDP packet loss and jitter in both directions
+    Loss,
+    /// TCP retransmits and sender CPU utilisation in both directions
+    Retransmits,
+}
+
+// Статистика повторов, отображаемая на хитмапе
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HeatmapStatistic {
+    /// Mean over repeated runs
+    Mean,
+    /// Sample standard deviation over repeated runs
+    Stddev,
+    /// Half-width of the 95% confidence interval of the mean
+    Ci95,
+}
+
+// Значения метрик одной пары MTU
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MetricValues {
+    pub upload_rcv_mbps: f64,
+    pub upload_send_mbps: f64,
+    pub download_rcv_mbps: f64,
+    pub download_send_mbps: f64,
+    pub upload_jitter_ms: f64,
+    pub upload_loss_percent: f64,
+    pub download_jitter_ms: f64,
+    pub download_loss_percent: f64,
+    pub upload_retransmits: f64,
+    pub download_retransmits: f64,
+    pub upload_peer_cpu_percent: f64,
+    pub download_server_cpu_percent: f64,
 }
 
 pub struct DataPoint {
@@ -53,4 +390,15 @@ pub struct DataPoint {
     pub upload_send_mbps: f64,
     pub download_rcv_mbps: f64,
     pub download_send_mbps: f64,
-}
\ No newline at end of file
+    pub upload_jitter_ms: f64,
+    pub upload_loss_percent: f64,
+    pub download_jitter_ms: f64,
+    pub download_loss_percent: f64,
+    pub upload_retransmits: f64,
+    pub download_retransmits: f64,
+    pub upload_peer_cpu_percent: f64,
+    pub download_server_cpu_percent: f64,
+    // Разброс повторов, нули для файлов без повторов
+    pub stddev: MetricValues,
+    pub ci95: MetricValues,
+}
@@ -0,0 +1,88 @@
+use crate::heatmap::error::HeatmapError;
+use crate::network::error::MtuError;
+use std::fmt::Display;
+use thiserror::Error;
+
+// Коды завершения процесса по категориям ошибок
+pub const EXIT_CONFIG: i32 = 2;
+pub const EXIT_MTU: i32 = 3;
+pub const EXIT_ENGINE: i32 = 4;
+pub const EXIT_CONNECTION: i32 = 5;
+pub const EXIT_PROTOCOL: i32 = 6;
+pub const EXIT_REMOTE: i32 = 7;
+pub const EXIT_ABORTED: i32 = 8;
+pub const EXIT_OUTPUT: i32 = 9;
+pub const EXIT_HEATMAP: i32 = 10;
+pub const EXIT_LAB: i32 = 11;
+pub const EXIT_ANALYSIS: i32 = 12;
+
+#[derive(Error, Debug)]
+pub enum AppError {
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+
+    #[error("MTU error: {0}")]
+    Mtu(#[from] MtuError),
+
+    #[error("Throughput engine error: {0}")]
+    Engine(String),
+
+    #[error("Connection error: {0}")]
+    Connection(String),
+
+    #[error("Protocol error: {0}")]
+    Protocol(String),
+
+    #[error("{side} reported an error: {reason}")]
+    Remote { side: String, reason: String },
+
+    #[error("{0} aborted the sweep")]
+    Aborted(String),
+
+    #[error("Output error: {0}")]
+    Output(String),
+
+    #[error("Heatmap error: {0}")]
+    Heatmap(#[from] HeatmapError),
+
+    #[error("Lab error: {0}")]
+    Lab(String),
+
+    #[error("Analysis error: {0}")]
+    Analysis(String),
+
+    #[error("{source} {context}")]
+    Context {
+        source: Box<AppError>,
+        context: String,
+    },
+}
+
+impl AppError {
+    // Функция для получения кода завершения процесса для ошибки
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            AppError::Config(_) => EXIT_CONFIG,
+            AppError::Mtu(_) => EXIT_MTU,
+            AppError::Engine(_) => EXIT_ENGINE,
+            AppError::Connection(_) => EXIT_CONNECTION,
+            AppError::Protocol(_) => EXIT_PROTOCOL,
+            AppError::Remote { .. } => EXIT_REMOTE,
+            AppError::Aborted(_) => EXIT_ABORTED,
+            AppError::Output(_) => EXIT_OUTPUT,
+            AppError::Heatmap(_) => EXIT_HEATMAP,
+            AppError::Lab(_) => EXIT_LAB,
+            AppError::Analysis(_) => EXIT_ANALYSIS,
+            AppError::Context { source, .. } => source.exit_code(),
+        }
+    }
+
+    // Функция для дополнения ошибки сведениями о том, что выполнялось в момент сбоя.
+    // Категория ошибки, а значит и код завершения, сохраняется.
+    pub fn context(self, context: impl Display) -> Self {
+        AppError::Context {
+            source: Box::new(self),
+            context: context.to_string(),
+        }
+    }
+}
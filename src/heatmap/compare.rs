@@ -0,0 +1,251 @@
+use clap::ValueEnum;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use super::renderer::{ColorScale, GridAxes};
+use super::{MetricMap, draw_panels, panel_metrics, statistic_values};
+use crate::data::models::{
+    CompareParameters, DataPoint, HeatmapDelta, HeatmapMetric, HeatmapStatistic,
+};
+use crate::heatmap::data_reader::read_csv_data;
+use crate::heatmap::error::HeatmapError;
+
+// Change of one metric at one MTU pair between the baseline and the candidate run
+#[derive(Debug, Clone)]
+pub struct CellDelta {
+    pub metric: &'static str,
+    pub server_mtu: u16,
+    pub peer_mtu: u16,
+    pub baseline: f64,
+    pub candidate: f64,
+    pub absolute: f64,
+    // None when the baseline value is zero
+    pub percent: Option<f64>,
+    pub higher_is_better: bool,
+}
+
+impl CellDelta {
+    // How much worse the candidate is, in percent of the baseline; positive for
+    // regressions and infinite when a zero baseline got worse
+    pub fn regression_percent(&self) -> f64 {
+        let worse = if self.higher_is_better {
+            -self.absolute
+        } else {
+            self.absolute
+        };
+        match self.percent {
+            Some(percent) if self.higher_is_better => -percent,
+            Some(percent) => percent,
+            None if worse > 0.0 => f64::INFINITY,
+            None => 0.0,
+        }
+    }
+
+    fn delta(&self, kind: HeatmapDelta) -> Option<f64> {
+        match kind {
+            HeatmapDelta::Absolute => Some(self.absolute),
+            HeatmapDelta::Percent => self.percent,
+        }
+    }
+}
+
+pub fn compare_runs(params: CompareParameters) -> Result<(), HeatmapError> {
+    println!(
+        "Comparing {} (candidate) against {} (baseline)",
+        params.candidate_filepath, params.baseline_filepath
+    );
+
+    let baseline = read_csv_data(&params.baseline_filepath)?;
+    let candidate = read_csv_data(&params.candidate_filepath)?;
+
+    let baseline_pairs: HashMap<(u16, u16), &DataPoint> =
+        baseline.iter().map(|p| ((p.server_mtu, p.peer_mtu), p)).collect();
+    let candidate_pairs: HashMap<(u16, u16), &DataPoint> =
+        candidate.iter().map(|p| ((p.server_mtu, p.peer_mtu), p)).collect();
+    let shared = baseline_pairs
+        .keys()
+        .filter(|pair| candidate_pairs.contains_key(*pair))
+        .count();
+    if shared == 0 {
+        return Err(HeatmapError::CSVParse(
+            "The two runs have no MTU pairs in common".to_string(),
+        ));
+    }
+    println!(
+        "{} MTU pairs in both runs, {} only in the baseline, {} only in the candidate",
+        shared,
+        baseline_pairs.len() - shared,
+        candidate_pairs.len() - shared
+    );
+
+    let deltas = metric_deltas(&baseline, &candidate);
+    print_summary(&deltas);
+    print_regressions(&deltas, params.metric, params.top);
+
+    // Axes cover the pairs measured in both runs
+    let axis = |mtu: fn(&CellDelta) -> u16| {
+        let mut mtus: Vec<u16> = deltas.iter().map(mtu).collect();
+        mtus.sort_unstable();
+        mtus.dedup();
+        mtus
+    };
+    let server_mtus = axis(|delta| delta.server_mtu);
+    let peer_mtus = axis(|delta| delta.peer_mtu);
+    let axes = GridAxes {
+        server_mtus: &server_mtus,
+        peer_mtus: &peer_mtus,
+        highlight: None,
+    };
+
+    let unit = match params.delta {
+        HeatmapDelta::Absolute => "change",
+        HeatmapDelta::Percent => "change in %",
+    };
+    let panels: Vec<(String, MetricMap, ColorScale)> = panel_metrics(params.metric)
+        .into_iter()
+        .map(|(title, _, scale)| {
+            let map = deltas
+                .iter()
+                .filter(|delta| delta.metric == title)
+                .filter_map(|delta| {
+                    let value = delta.delta(params.delta)?;
+                    Some(((delta.server_mtu, delta.peer_mtu), value))
+                })
+                .collect();
+            let higher_is_better = scale == ColorScale::HigherIsBetter;
+            (
+                format!("{}, {}", title, unit),
+                map,
+                ColorScale::Diverging { higher_is_better },
+            )
+        })
+        .collect();
+
+    draw_panels(&params.heatmap_filepath, &axes, &panels)?;
+
+    println!(
+        "Done generating difference heatmap. File saved at: {}",
+        params.heatmap_filepath
+    );
+    Ok(())
+}
+
+// Align both runs by (server_mtu, peer_mtu) and compute the change of every
+// metric at every pair measured in both
+pub fn metric_deltas(baseline: &[DataPoint], candidate: &[DataPoint]) -> Vec<CellDelta> {
+    let candidate_pairs: HashMap<(u16, u16), &DataPoint> =
+        candidate.iter().map(|p| ((p.server_mtu, p.peer_mtu), p)).collect();
+
+    let mut deltas = Vec::new();
+    for before in baseline {
+        let Some(after) = candidate_pairs.get(&(before.server_mtu, before.peer_mtu)) else {
+            continue;
+        };
+        let before_values = statistic_values(before, HeatmapStatistic::Mean);
+        let after_values = statistic_values(after, HeatmapStatistic::Mean);
+
+        for group in HeatmapMetric::value_variants() {
+            for (title, metric, scale) in panel_metrics(*group) {
+                let baseline = metric(&before_values);
+                let candidate = metric(&after_values);
+                let absolute = candidate - baseline;
+                let percent = (baseline != 0.0).then(|| absolute / baseline.abs() * 100.0);
+
+                deltas.push(CellDelta {
+                    metric: title,
+                    server_mtu: before.server_mtu,
+                    peer_mtu: before.peer_mtu,
+                    baseline,
+                    candidate,
+                    absolute,
+                    percent,
+                    higher_is_better: scale == ColorScale::HigherIsBetter,
+                });
+            }
+        }
+    }
+
+    deltas
+}
+
+// Mean change and the worst cell of every metric
+fn print_summary(deltas: &[CellDelta]) {
+    println!("Change per metric (candidate vs baseline):");
+
+    for group in HeatmapMetric::value_variants() {
+        for (title, _, _) in panel_metrics(*group) {
+            let cells: Vec<&CellDelta> = deltas.iter().filter(|d| d.metric == title).collect();
+            let count = cells.len().max(1) as f64;
+            let mean_absolute = cells.iter().map(|d| d.absolute).sum::<f64>() / count;
+            let mean_baseline = cells.iter().map(|d| d.baseline).sum::<f64>() / count;
+
+            // Percent of the mean baseline over the same cells as the absolute mean
+            let mean = if mean_baseline == 0.0 {
+                format!("mean {:+.2}", mean_absolute)
+            } else {
+                let mean_percent = mean_absolute / mean_baseline.abs() * 100.0;
+                format!("mean {:+.2} ({:+.1}%)", mean_absolute, mean_percent)
+            };
+            let worst = cells
+                .iter()
+                .filter(|d| d.regression_percent() > 0.0)
+                .max_by(|a, b| severity(a, b))
+                .map_or_else(
+                    || "no regressions".to_string(),
+                    |d| {
+                        format!(
+                            "worst {} at server MTU {}, peer MTU {}",
+                            change(d),
+                            d.server_mtu,
+                            d.peer_mtu
+                        )
+                    },
+                );
+
+            println!("  {}: {}, {}", title, mean, worst);
+        }
+    }
+}
+
+// Largest regressions among the metrics of the heatmap
+fn print_regressions(deltas: &[CellDelta], metric: HeatmapMetric, top: usize) {
+    let titles: Vec<&str> = panel_metrics(metric).iter().map(|(title, _, _)| *title).collect();
+    let mut regressions: Vec<&CellDelta> = deltas
+        .iter()
+        .filter(|d| titles.contains(&d.metric) && d.regression_percent() > 0.0)
+        .collect();
+    regressions.sort_by(|a, b| severity(b, a));
+
+    if regressions.is_empty() {
+        println!("No regressions in {:?} metrics", metric);
+        return;
+    }
+
+    println!("Largest regressions in {:?} metrics:", metric);
+    for delta in regressions.iter().take(top) {
+        println!(
+            "  server MTU {}, peer MTU {}: {} {:.2} -> {:.2} ({})",
+            delta.server_mtu,
+            delta.peer_mtu,
+            delta.metric,
+            delta.baseline,
+            delta.candidate,
+            change(delta)
+        );
+    }
+}
+
+// Order of regressions by severity; cells that got worse from a zero baseline
+// all have infinite percent, so the absolute change breaks the tie
+fn severity(a: &CellDelta, b: &CellDelta) -> Ordering {
+    a.regression_percent()
+        .total_cmp(&b.regression_percent())
+        .then(a.absolute.abs().total_cmp(&b.absolute.abs()))
+}
+
+// Change of one cell as "+1.23, +4.5%"
+fn change(delta: &CellDelta) -> String {
+    match delta.percent {
+        Some(percent) => format!("{:+.2}, {:+.1}%", delta.absolute, percent),
+        None => format!("{:+.2}", delta.absolute),
+    }
+}
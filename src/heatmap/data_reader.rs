@@ -1,7 +1,8 @@
-use crate::data::models::DataPoint;
+use crate::data::models::{DataPoint, MetricValues};
 use crate::heatmap::error::HeatmapError;
 use std::fs::File;
 use std::io::{self, BufRead};
+use std::str::FromStr;
 
 pub fn read_csv_data(filepath: &str) -> Result<Vec<DataPoint>, HeatmapError> {
     let file = File::open(filepath)?;
@@ -9,8 +10,24 @@ pub fn read_csv_data(filepath: &str) -> Result<Vec<DataPoint>, HeatmapError> {
     let mut data = Vec::new();
     let mut lines = reader.lines();
 
-    // Skip header
-    let _ = lines.next();
+    // The header is used to locate optional columns added in newer versions
+    let header = match lines.next() {
+        Some(line) => line?,
+        None => return Ok(data),
+    };
+    let columns: Vec<&str> = header.split(',').map(str::trim).collect();
+    let column = |name: &str| columns.iter().position(|&c| c == name);
+
+    let upload_jitter_idx = column("upload_jitter_ms");
+    let upload_loss_idx = column("upload_loss_percent");
+    let download_jitter_idx = column("download_jitter_ms");
+    let download_loss_idx = column("download_loss_percent");
+    let upload_retransmits_idx = column("upload_retransmits");
+    let download_retransmits_idx = column("download_retransmits");
+    let upload_peer_cpu_idx = column("upload_peer_cpu_percent");
+    let download_server_cpu_idx = column("download_server_cpu_percent");
+    let stddev_columns = SpreadColumns::locate(&columns, "stddev");
+    let ci95_columns = SpreadColumns::locate(&columns, "ci95");
 
     for line_result in lines {
         let line = line_result?;
@@ -51,8 +68,107 @@ pub fn read_csv_data(filepath: &str) -> Result<Vec<DataPoint>, HeatmapError> {
             upload_send_mbps,
             download_rcv_mbps,
             download_send_mbps,
+            upload_jitter_ms: optional_field(&parts, upload_jitter_idx, "upload_jitter_ms")?,
+            upload_loss_percent: optional_field(&parts, upload_loss_idx, "upload_loss_percent")?,
+            download_jitter_ms: optional_field(&parts, download_jitter_idx, "download_jitter_ms")?,
+            download_loss_percent: optional_field(
+                &parts,
+                download_loss_idx,
+                "download_loss_percent",
+            )?,
+            upload_retransmits: optional_field(
+                &parts,
+                upload_retransmits_idx,
+                "upload_retransmits",
+            )?,
+            download_retransmits: optional_field(
+                &parts,
+                download_retransmits_idx,
+                "download_retransmits",
+            )?,
+            upload_peer_cpu_percent: optional_field(
+                &parts,
+                upload_peer_cpu_idx,
+                "upload_peer_cpu_percent",
+            )?,
+            download_server_cpu_percent: optional_field(
+                &parts,
+                download_server_cpu_idx,
+                "download_server_cpu_percent",
+            )?,
+            stddev: stddev_columns.read(&parts)?,
+            ci95: ci95_columns.read(&parts)?,
         });
     }
 
     Ok(data)
 }
+
+// Positions of the `<metric>_<statistic>` columns written for repeated runs
+struct SpreadColumns {
+    names: Vec<String>,
+    indices: Vec<Option<usize>>,
+}
+
+impl SpreadColumns {
+    const METRICS: [&'static str; 12] = [
+        "upload_rcv_mbps",
+        "upload_send_mbps",
+        "download_rcv_mbps",
+        "download_send_mbps",
+        "upload_jitter_ms",
+        "upload_loss_percent",
+        "download_jitter_ms",
+        "download_loss_percent",
+        "upload_retransmits",
+        "download_retransmits",
+        "upload_peer_cpu_percent",
+        "download_server_cpu_percent",
+    ];
+
+    fn locate(columns: &[&str], statistic: &str) -> Self {
+        let names: Vec<String> = Self::METRICS
+            .iter()
+            .map(|metric| format!("{}_{}", metric, statistic))
+            .collect();
+        let indices = names
+            .iter()
+            .map(|name| columns.iter().position(|c| c == name))
+            .collect();
+
+        SpreadColumns { names, indices }
+    }
+
+    fn read(&self, parts: &[&str]) -> Result<MetricValues, HeatmapError> {
+        let field = |i: usize| optional_field::<f64>(parts, self.indices[i], &self.names[i]);
+
+        Ok(MetricValues {
+            upload_rcv_mbps: field(0)?,
+            upload_send_mbps: field(1)?,
+            download_rcv_mbps: field(2)?,
+            download_send_mbps: field(3)?,
+            upload_jitter_ms: field(4)?,
+            upload_loss_percent: field(5)?,
+            download_jitter_ms: field(6)?,
+            download_loss_percent: field(7)?,
+            upload_retransmits: field(8)?,
+            download_retransmits: field(9)?,
+            upload_peer_cpu_percent: field(10)?,
+            download_server_cpu_percent: field(11)?,
+        })
+    }
+}
+
+// Columns missing from older CSV files default to zero
+fn optional_field<T: FromStr + Default>(
+    parts: &[&str],
+    index: Option<usize>,
+    name: &str,
+) -> Result<T, HeatmapError> {
+    match index.and_then(|idx| parts.get(idx)) {
+        Some(value) => value
+            .parse::<T>()
+            .map_err(|_| HeatmapError::CSVParse(format!("Invalid {}: {}", name, value))),
+        None => Ok(T::default()),
+    }
+}
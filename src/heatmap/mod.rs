@@ -1,13 +1,23 @@
-mod data_reader;
-mod error;
+pub mod compare;
+pub mod data_reader;
+pub mod error;
 mod renderer;
 
 use plotters::prelude::*;
 use std::collections::HashMap;
-use renderer::{draw_heatmap, max_positive_value};
-use crate::data::models::HeatmapParameters;
+use renderer::{ColorScale, GridAxes, draw_heatmap};
+use crate::data::models::{
+    DataPoint, HeatmapMetric, HeatmapParameters, HeatmapStatistic, MetricValues,
+};
 use crate::heatmap::data_reader::read_csv_data;
 use crate::heatmap::error::HeatmapError;
+use crate::utils::metadata::load_metadata;
+
+// Metric values keyed by (server_mtu, peer_mtu)
+type MetricMap = HashMap<(u16, u16), f64>;
+
+// Panel title, metric accessor and which direction is better
+type PanelMetric = (&'static str, fn(&MetricValues) -> f64, ColorScale);
 
 pub fn generate_heatmap(params: HeatmapParameters) -> Result<(), HeatmapError> {
     let log_filepath = &params.log_filepath;
@@ -50,29 +60,136 @@ pub fn generate_heatmap(params: HeatmapParameters) -> Result<(), HeatmapError> {
         mtus
     };
 
-    // Create a mapping for the metrics
-    let mut upload_rcv_map = HashMap::new();
-    let mut upload_send_map = HashMap::new();
-    let mut download_rcv_map = HashMap::new();
-    let mut download_send_map = HashMap::new();
-
-    for point in &data {
-        let key = (point.server_mtu, point.peer_mtu);
-        upload_rcv_map.insert(key, point.upload_rcv_mbps);
-        upload_send_map.insert(key, point.upload_send_mbps);
-        download_rcv_map.insert(key, point.download_rcv_mbps);
-        download_send_map.insert(key, point.download_send_mbps);
+    // Metrics of the selected panels
+    let metrics = panel_metrics(params.metric);
+
+    // Create a mapping for the selected statistic; spread is better when lower
+    let panels: Vec<(String, MetricMap, ColorScale)> = metrics
+        .into_iter()
+        .map(|(title, metric, scale)| {
+            let map = metric_map(&data, |p| metric(&statistic_values(p, params.statistic)));
+            match params.statistic {
+                HeatmapStatistic::Mean => (title.to_string(), map, scale),
+                HeatmapStatistic::Stddev => {
+                    (format!("{}, stddev", title), map, ColorScale::LowerIsBetter)
+                }
+                HeatmapStatistic::Ci95 => {
+                    (format!("{}, 95% CI", title), map, ColorScale::LowerIsBetter)
+                }
+            }
+        })
+        .collect();
+
+    // Mark the MTU pair the interfaces had before the sweep, if known
+    let highlight = load_metadata(log_filepath).and_then(|metadata| {
+        let server_mtu = u16::try_from(metadata.server_current_mtu?).ok()?;
+        let peer_mtu = u16::try_from(metadata.peer_current_mtu?).ok()?;
+        Some((server_mtu, peer_mtu))
+    });
+    if let Some((server_mtu, peer_mtu)) = highlight {
+        println!(
+            "Marking current configuration: server MTU {}, peer MTU {}",
+            server_mtu, peer_mtu
+        );
     }
 
-    // Find max value for color scale
-    let max_upload_rcv = max_positive_value(&upload_rcv_map);
-    let max_upload_send = max_positive_value(&upload_send_map);
-    let max_download_rcv = max_positive_value(&download_rcv_map);
-    let max_download_send = max_positive_value(&download_send_map);
+    let axes = GridAxes {
+        server_mtus: &server_mtus_sorted,
+        peer_mtus: &peer_mtus_sorted,
+        highlight,
+    };
+
+    draw_panels(heatmap_filepath, &axes, &panels)?;
+
+    println!(
+        "Done generating heatmap. File saved at: {}",
+        heatmap_filepath
+    );
+    Ok(())
+}
 
+// Metrics of the four panels drawn for a heatmap metric
+fn panel_metrics(metric: HeatmapMetric) -> [PanelMetric; 4] {
+    match metric {
+        HeatmapMetric::Bandwidth => [
+            (
+                "Upload Rcv Bandwidth (Mbps)",
+                |v| v.upload_rcv_mbps,
+                ColorScale::HigherIsBetter,
+            ),
+            (
+                "Upload Send Bandwidth (Mbps)",
+                |v| v.upload_send_mbps,
+                ColorScale::HigherIsBetter,
+            ),
+            (
+                "Download Rcv Bandwidth (Mbps)",
+                |v| v.download_rcv_mbps,
+                ColorScale::HigherIsBetter,
+            ),
+            (
+                "Download Send Bandwidth (Mbps)",
+                |v| v.download_send_mbps,
+                ColorScale::HigherIsBetter,
+            ),
+        ],
+        HeatmapMetric::Loss => [
+            (
+                "Upload Packet Loss (%)",
+                |v| v.upload_loss_percent,
+                ColorScale::LowerIsBetter,
+            ),
+            (
+                "Upload Jitter (ms)",
+                |v| v.upload_jitter_ms,
+                ColorScale::LowerIsBetter,
+            ),
+            (
+                "Download Packet Loss (%)",
+                |v| v.download_loss_percent,
+                ColorScale::LowerIsBetter,
+            ),
+            (
+                "Download Jitter (ms)",
+                |v| v.download_jitter_ms,
+                ColorScale::LowerIsBetter,
+            ),
+        ],
+        // The sender is the peer for uploads and the server for downloads
+        HeatmapMetric::Retransmits => [
+            (
+                "Upload TCP Retransmits",
+                |v| v.upload_retransmits,
+                ColorScale::LowerIsBetter,
+            ),
+            (
+                "Upload Sender CPU (%)",
+                |v| v.upload_peer_cpu_percent,
+                ColorScale::LowerIsBetter,
+            ),
+            (
+                "Download TCP Retransmits",
+                |v| v.download_retransmits,
+                ColorScale::LowerIsBetter,
+            ),
+            (
+                "Download Sender CPU (%)",
+                |v| v.download_server_cpu_percent,
+                ColorScale::LowerIsBetter,
+            ),
+        ],
+    }
+}
+
+// Draw a 2x2 grid of heatmap panels into a PNG file
+fn draw_panels(
+    heatmap_filepath: &str,
+    axes: &GridAxes,
+    panels: &[(String, MetricMap, ColorScale)],
+) -> Result<(), HeatmapError> {
     let base_size = 1200;
-    let width_multiplier = (peer_mtus_sorted.len() as f32 / 10.0).max(1.0);
-    let height_multiplier = (server_mtus_sorted.len() as f32 / 10.0).max(1.0);
+    let width_multiplier = (axes.peer_mtus.len() as f32 / 10.0).max(1.0);
+    let height_multiplier = (axes.server_mtus.len() as f32 / 10.0).max(1.0);
 
     let width = (base_size as f32 * width_multiplier) as u32;
     let height = (base_size as f32 * height_multiplier) as u32;
@@ -83,51 +200,50 @@ pub fn generate_heatmap(params: HeatmapParameters) -> Result<(), HeatmapError> {
 
     let areas = root.split_evenly((2, 2));
 
-    // Upper left: Upload Receive
-    draw_heatmap(
-        &areas[0].margin(10, 20, 10, 20),
-        "Upload Rcv Bandwidth (Mbps)",
-        &server_mtus_sorted,
-        &peer_mtus_sorted,
-        &upload_rcv_map,
-        max_upload_rcv,
-    )?;
-
-    // Upper right: Upload Send
-    draw_heatmap(
-        &areas[1].margin(10, 20, 20, 10),
-        "Upload Send Bandwidth (Mbps)",
-        &server_mtus_sorted,
-        &peer_mtus_sorted,
-        &upload_send_map,
-        max_upload_send,
-    )?;
-
-    // Lower left: Download Receive
-    draw_heatmap(
-        &areas[2].margin(20, 10, 10, 20),
-        "Download Rcv Bandwidth (Mbps)",
-        &server_mtus_sorted,
-        &peer_mtus_sorted,
-        &download_rcv_map,
-        max_download_rcv,
-    )?;
-
-    // Lower right: Download Send
-    draw_heatmap(
-        &areas[3].margin(10, 20, 20, 10),
-        "Download Send Bandwidth (Mbps)",
-        &server_mtus_sorted,
-        &peer_mtus_sorted,
-        &download_send_map,
-        max_download_send,
-    )?;
+    // Upper left, upper right, lower left, lower right
+    let margins = [(10, 20, 10, 20), (10, 20, 20, 10), (20, 10, 10, 20), (10, 20, 20, 10)];
+
+    for ((area, (top, bottom, left, right)), (title, map, scale)) in
+        areas.iter().zip(margins).zip(panels)
+    {
+        draw_heatmap(
+            &area.margin(top, bottom, left, right),
+            title,
+            axes,
+            map,
+            scale.max_value(map),
+            *scale,
+        )?;
+    }
 
     root.present()?;
-
-    println!(
-        "Done generating heatmap. File saved at: {}",
-        heatmap_filepath
-    );
     Ok(())
 }
+
+// Values of the chosen statistic for one MTU pair
+fn statistic_values(point: &DataPoint, statistic: HeatmapStatistic) -> MetricValues {
+    match statistic {
+        HeatmapStatistic::Mean => MetricValues {
+            upload_rcv_mbps: point.upload_rcv_mbps,
+            upload_send_mbps: point.upload_send_mbps,
+            download_rcv_mbps: point.download_rcv_mbps,
+            download_send_mbps: point.download_send_mbps,
+            upload_jitter_ms: point.upload_jitter_ms,
+            upload_loss_percent: point.upload_loss_percent,
+            download_jitter_ms: point.download_jitter_ms,
+            download_loss_percent: point.download_loss_percent,
+            upload_retransmits: point.upload_retransmits,
+            download_retransmits: point.download_retransmits,
+            upload_peer_cpu_percent: point.upload_peer_cpu_percent,
+            download_server_cpu_percent: point.download_server_cpu_percent,
+        },
+        HeatmapStatistic::Stddev => point.stddev,
+        HeatmapStatistic::Ci95 => point.ci95,
+    }
+}
+
+fn metric_map(data: &[DataPoint], metric: impl Fn(&DataPoint) -> f64) -> MetricMap {
+    data.iter()
+        .map(|point| ((point.server_mtu, point.peer_mtu), metric(point)))
+        .collect()
+}
@@ -4,17 +4,92 @@ use std::collections::HashMap;
 
 use crate::heatmap::error::HeatmapError;
 
+// Direction of the color scale: which end of the value range is shaded darkest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorScale {
+    // Bandwidth-like metrics, zero or negative values mark failed tests
+    HigherIsBetter,
+    // Loss-like metrics, zero is the best possible value
+    LowerIsBetter,
+    // Signed differences centred on zero, shaded green for improvements and
+    // red for regressions of a metric where higher or lower is better
+    Diverging { higher_is_better: bool },
+}
+
+impl ColorScale {
+    // Normalized intensity from 0 to 1, or None for cells that should be drawn as errors
+    fn intensity(self, value: Option<f64>, max_value: f64) -> Option<f64> {
+        match self {
+            ColorScale::HigherIsBetter => {
+                let v = value.unwrap_or(0.0);
+                if v <= 0.0 {
+                    return None;
+                }
+                Some((v / max_value).min(1.0))
+            }
+            ColorScale::LowerIsBetter => {
+                let v = value?;
+                if v < 0.0 {
+                    return None;
+                }
+                if max_value <= 0.0 {
+                    return Some(1.0);
+                }
+                Some(1.0 - (v / max_value).min(1.0))
+            }
+            ColorScale::Diverging { .. } => {
+                let v = value?;
+                if max_value <= 0.0 {
+                    return Some(0.0);
+                }
+                Some((v.abs() / max_value).min(1.0))
+            }
+        }
+    }
+
+    // Whether a value counts as an improvement on a diverging scale
+    fn is_improvement(self, value: f64) -> bool {
+        match self {
+            ColorScale::Diverging { higher_is_better } => (value > 0.0) == higher_is_better,
+            _ => true,
+        }
+    }
+
+    // Largest value the color scale should reach for the given cells
+    pub fn max_value(self, map: &HashMap<(u16, u16), f64>) -> f64 {
+        match self {
+            ColorScale::Diverging { .. } => map.values().fold(0.0, |max, &v| max.max(v.abs())),
+            _ => max_positive_value(map),
+        }
+    }
+}
+
+// Sorted MTU values of both axes and an optional cell to outline
+pub struct GridAxes<'a> {
+    pub server_mtus: &'a [u16],
+    pub peer_mtus: &'a [u16],
+    // (server_mtu, peer_mtu) of the interface configuration before the sweep
+    pub highlight: Option<(u16, u16)>,
+}
+
 pub fn draw_heatmap<DB: DrawingBackend>(
     area: &DrawingArea<DB, Shift>,
     title: &str,
-    server_mtus: &[u16],
-    peer_mtus: &[u16],
+    axes: &GridAxes,
     data_map: &HashMap<(u16, u16), f64>,
     max_value: f64,
+    scale: ColorScale,
 ) -> Result<(), HeatmapError> {
+    let server_mtus = axes.server_mtus;
+    let peer_mtus = axes.peer_mtus;
+
+    // Shrink long titles so they fit the panel width
+    let (width, _) = area.dim_in_pixel();
+    let caption_size = (width as f64 / (title.len() as f64 * 0.55)).min(48.0);
+
     // Create a chart with integer coordinates instead of segmented
     let mut chart_builder = ChartBuilder::on(area)
-        .caption(title, ("sans-serif", 48))
+        .caption(title, ("sans-serif", caption_size))
         .margin(5)
         .x_label_area_size(60)
         .y_label_area_size(90)
@@ -41,14 +116,19 @@ pub fn draw_heatmap<DB: DrawingBackend>(
         .label_style(("sans-serif", 21))
         .draw()?;
 
-    // Define a color map from white to green
-    let color_mapping = |v: f64| -> RGBColor {
-        if v <= 0.0 {
-            // Light gray color for errors (-1.0)
+    // Define a color map from white to green, or to red for regressions
+    let color_mapping = |v: Option<f64>| -> RGBColor {
+        let Some(intensity) = scale.intensity(v, max_value) else {
+            // Light gray color for errors (-1.0) and missing cells
             return RGBColor(240, 240, 240);
-        }
+        };
 
-        let intensity = (v / max_value).min(1.0); // Normalize from 0 to 1
+        if !scale.is_improvement(v.unwrap_or(0.0)) {
+            // Interpolate from white (255, 255, 255) to red (200, 40, 40)
+            let r = (255.0 - (255.0 - 200.0) * intensity) as u8;
+            let gb = (255.0 - (255.0 - 40.0) * intensity) as u8;
+            return RGBColor(r, gb, gb);
+        }
 
         // Interpolate from white (255, 255, 255) to green (0, 155, 119)
         let r = (255.0 * (1.0 - intensity)) as u8; // From 255 to 0
@@ -62,10 +142,7 @@ pub fn draw_heatmap<DB: DrawingBackend>(
     chart_builder.draw_series(server_mtus.iter().enumerate().flat_map(
         |(y_idx, &server_mtu)| {
             peer_mtus.iter().enumerate().map(move |(x_idx, &peer_mtu)| {
-                let value = data_map
-                    .get(&(server_mtu, peer_mtu))
-                    .copied()
-                    .unwrap_or(0.0);
+                let value = data_map.get(&(server_mtu, peer_mtu)).copied();
                 let color = color_mapping(value);
 
                 Rectangle::new([(x_idx, y_idx), (x_idx + 1, y_idx + 1)], color.filled())
@@ -73,15 +150,26 @@ pub fn draw_heatmap<DB: DrawingBackend>(
         },
     ))?;
 
+    // Outline the current configuration cell
+    if let Some((server_mtu, peer_mtu)) = axes.highlight {
+        let x_idx = peer_mtus.iter().position(|&v| v == peer_mtu);
+        let y_idx = server_mtus.iter().position(|&v| v == server_mtu);
+
+        if let (Some(x_idx), Some(y_idx)) = (x_idx, y_idx) {
+            chart_builder.draw_series(std::iter::once(Rectangle::new(
+                [(x_idx, y_idx), (x_idx + 1, y_idx + 1)],
+                RED.stroke_width(4),
+            )))?;
+        }
+    }
+
     // Add value labels
     chart_builder.draw_series(server_mtus.iter().enumerate().flat_map(
         |(y_idx, &server_mtu)| {
             peer_mtus.iter().enumerate().map(move |(x_idx, &peer_mtu)| {
-                let value = data_map
-                    .get(&(server_mtu, peer_mtu))
-                    .copied()
-                    .unwrap_or(0.0);
-                let text_color = if value > max_value * 0.7 {
+                let value = data_map.get(&(server_mtu, peer_mtu)).copied();
+                let intensity = scale.intensity(value, max_value).unwrap_or(0.0);
+                let text_color = if intensity > 0.7 {
                     WHITE
                 } else {
                     BLACK
@@ -94,8 +182,15 @@ pub fn draw_heatmap<DB: DrawingBackend>(
                     _ => 14,
                 };
 
+                // Differences are signed, and cells missing from either run have none
+                let label = match (scale, value) {
+                    (ColorScale::Diverging { .. }, Some(value)) => format!("{:+.1}", value),
+                    (ColorScale::Diverging { .. }, None) => String::new(),
+                    _ => format!("{:.1}", value.unwrap_or(0.0)),
+                };
+
                 Text::new(
-                    format!("{:.1}", value),
+                    label,
                     (x_idx, y_idx + 1),
                     ("sans-serif", font_size).into_font().color(&text_color),
                 )
@@ -0,0 +1,291 @@
+mod topology;
+
+use clap::ValueEnum;
+use signal_hook::consts::{SIGINT, SIGTERM};
+use std::env;
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use topology::{LabSide, LabTopology, SERVER_TUNNEL_IP, SERVER_UNDERLAY_IP, TUNNEL_INTERFACE};
+use wg_mtu_finder::{AppError, LabParameters};
+
+// Строка вывода сервера, после которой к нему можно подключаться
+const SERVER_READY_LINE: &str = "Waiting for peer connection";
+// Сколько ждать готовности сервера
+const SERVER_READY_TIMEOUT: Duration = Duration::from_secs(10);
+// Интервал проверки завершения процессов
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Сервер или пир, запущенный внутри пространства имен стенда
+struct LabProcess {
+    name: &'static str,
+    child: Child,
+    output: Vec<JoinHandle<()>>,
+    status: Option<ExitStatus>,
+}
+
+// Функция для запуска перебора на стенде: создает пространства имен и туннель,
+// запускает в них сервер и пира и удаляет стенд после завершения
+pub fn run_lab(params: LabParameters) -> Result<(), AppError> {
+    check_tools(&params)?;
+
+    // По сигналу не выходим сразу: сначала останавливаем стороны и удаляем стенд
+    let stop = Arc::new(AtomicBool::new(false));
+    for signal in [SIGINT, SIGTERM] {
+        signal_hook::flag::register(signal, Arc::clone(&stop))
+            .map_err(|e| AppError::Lab(format!("Failed to install signal handler: {}", e)))?;
+    }
+
+    let topology = LabTopology::create(&params)?;
+    let result = run_sides(&params, &topology, &stop);
+    drop(topology);
+    result
+}
+
+// Функция для проверки наличия нужных утилит
+fn check_tools(params: &LabParameters) -> Result<(), AppError> {
+    let mut tools = vec![("ip", "iproute2"), ("wg", "wireguard-tools")];
+    if params.delay_ms.is_some() || params.loss_percent.is_some() {
+        tools.push(("tc", "iproute2"));
+    }
+
+    for (tool, package) in tools {
+        let found = Command::new("which")
+            .arg(tool)
+            .output()
+            .map(|output| output.status.success())
+            .unwrap_or(false);
+        if !found {
+            return Err(AppError::Lab(format!(
+                "{} is not installed or not in PATH (package {})",
+                tool, package
+            )));
+        }
+    }
+    Ok(())
+}
+
+// Функция для запуска сервера и пира и ожидания их завершения
+fn run_sides(
+    params: &LabParameters,
+    topology: &LabTopology,
+    stop: &AtomicBool,
+) -> Result<(), AppError> {
+    let (ready_sender, ready_receiver) = mpsc::channel();
+    let mut server = spawn_side(
+        "server",
+        &topology.server,
+        &server_args(params),
+        Some(ready_sender),
+    )?;
+
+    // Ждем, пока сервер начнет принимать подключения
+    let started = Instant::now();
+    loop {
+        if ready_receiver.recv_timeout(POLL_INTERVAL).is_ok() {
+            break;
+        }
+        let reason = if stop.load(Ordering::SeqCst) {
+            "Interrupted before the sweep started".to_string()
+        } else if server.poll() {
+            format!("Server exited early with {}", server.status.unwrap_or_default())
+        } else if started.elapsed() >= SERVER_READY_TIMEOUT {
+            "Server did not start in time".to_string()
+        } else {
+            continue;
+        };
+
+        server.terminate();
+        server.wait();
+        return Err(AppError::Lab(reason));
+    }
+
+    let mut peer = spawn_side("peer", &topology.peer, &peer_args(params), None)?;
+
+    // Ждем завершения обеих сторон; по сигналу просим их прервать перебор
+    let mut terminated = false;
+    loop {
+        let server_done = server.poll();
+        let peer_done = peer.poll();
+        if server_done && peer_done {
+            break;
+        }
+
+        if stop.load(Ordering::SeqCst) && !terminated {
+            println!("Stopping lab sweep");
+            server.terminate();
+            peer.terminate();
+            terminated = true;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    server.wait();
+    peer.wait();
+
+    if terminated {
+        return Err(AppError::Lab("Interrupted by signal".to_string()));
+    }
+    // Ошибку пира показываем в первую очередь, сервер обычно лишь отражает ее
+    for side in [&peer, &server] {
+        if let Some(status) = side.status.filter(|status| !status.success()) {
+            return Err(AppError::Lab(format!("{} exited with {}", side.name, status)));
+        }
+    }
+
+    println!("Lab sweep completed, results saved to {}", params.csv_file);
+    Ok(())
+}
+
+// Аргументы сервера внутри стенда
+fn server_args(params: &LabParameters) -> Vec<String> {
+    vec![
+        "server".to_string(),
+        "--interface".to_string(),
+        TUNNEL_INTERFACE.to_string(),
+        "--tunnel-ip".to_string(),
+        SERVER_TUNNEL_IP.to_string(),
+        "--min-mtu".to_string(),
+        params.min_mtu.to_string(),
+        "--max-mtu".to_string(),
+        params.max_mtu.to_string(),
+        "--step".to_string(),
+        params.step.to_string(),
+        "--engine".to_string(),
+        value_name(&params.engine),
+        "--strategy".to_string(),
+        value_name(&params.strategy),
+    ]
+}
+
+// Аргументы пира внутри стенда. Управляющее соединение идет мимо туннеля,
+// чтобы смена MTU не обрывала его.
+fn peer_args(params: &LabParameters) -> Vec<String> {
+    vec![
+        "peer".to_string(),
+        "--interface".to_string(),
+        TUNNEL_INTERFACE.to_string(),
+        "--server-ip".to_string(),
+        SERVER_TUNNEL_IP.to_string(),
+        "--control-ip".to_string(),
+        SERVER_UNDERLAY_IP.to_string(),
+        "--protocol".to_string(),
+        value_name(&params.protocol),
+        "--duration".to_string(),
+        params.duration_secs.to_string(),
+        "--repeats".to_string(),
+        params.repeats.to_string(),
+        "--csv-file".to_string(),
+        params.csv_file.clone(),
+    ]
+}
+
+// Имя значения перечисления в командной строке
+fn value_name<T: ValueEnum>(value: &T) -> String {
+    value
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+// Функция для запуска этой же программы внутри пространства имен стороны.
+// Вывод пересылается с префиксом стороны; ready получает сигнал готовности сервера.
+fn spawn_side(
+    name: &'static str,
+    side: &LabSide,
+    args: &[String],
+    ready: Option<Sender<()>>,
+) -> Result<LabProcess, AppError> {
+    let program = env::current_exe()
+        .map_err(|e| AppError::Lab(format!("Failed to locate own executable: {}", e)))?;
+
+    println!(
+        "Starting {} in namespace {} ({})",
+        name, side.namespace, side.tunnel_ip
+    );
+    let mut child = Command::new("ip")
+        .args(["netns", "exec", &side.namespace])
+        .arg(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| AppError::Lab(format!("Failed to start {}: {}", name, e)))?;
+
+    let mut output = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        output.push(forward_output(name, stdout, false, ready));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        output.push(forward_output(name, stderr, true, None));
+    }
+
+    Ok(LabProcess {
+        name,
+        child,
+        output,
+        status: None,
+    })
+}
+
+// Функция для пересылки вывода стороны построчно с префиксом
+fn forward_output<R: Read + Send + 'static>(
+    name: &'static str,
+    source: R,
+    is_stderr: bool,
+    ready: Option<Sender<()>>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(source).lines().map_while(Result::ok) {
+            if is_stderr {
+                eprintln!("[{}] {}", name, line);
+            } else {
+                println!("[{}] {}", name, line);
+            }
+
+            if line.contains(SERVER_READY_LINE)
+                && let Some(ready) = &ready
+            {
+                let _ = ready.send(());
+            }
+        }
+    })
+}
+
+impl LabProcess {
+    // Функция для проверки завершения процесса; true, если он завершился
+    fn poll(&mut self) -> bool {
+        if self.status.is_none() {
+            self.status = self.child.try_wait().ok().flatten();
+        }
+        self.status.is_some()
+    }
+
+    // Функция для отправки SIGTERM: сторона сообщит другой об остановке
+    // и вернет исходный MTU
+    fn terminate(&mut self) {
+        if self.poll() {
+            return;
+        }
+        if let Ok(pid) = libc::pid_t::try_from(self.child.id()) {
+            // SAFETY: обычный системный вызов, сигнал получает только наш дочерний процесс
+            unsafe {
+                libc::kill(pid, libc::SIGTERM);
+            }
+        }
+    }
+
+    // Функция для ожидания завершения процесса и пересылки его вывода
+    fn wait(&mut self) {
+        if self.status.is_none() {
+            self.status = self.child.wait().ok();
+        }
+        for handle in self.output.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
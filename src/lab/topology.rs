@@ -0,0 +1,221 @@
+use std::io::Write;
+use std::process::{self, Command, Stdio};
+use wg_mtu_finder::{AppError, LabParameters};
+
+// Имя интерфейса WireGuard внутри каждого пространства имен
+pub const TUNNEL_INTERFACE: &str = "wg-lab";
+// Адреса сети под туннелем и внутри туннеля (диапазон RFC 2544 для стендов)
+pub const SERVER_UNDERLAY_IP: &str = "198.18.0.1";
+const PEER_UNDERLAY_IP: &str = "198.18.0.2";
+const UNDERLAY_PREFIX: u8 = 30;
+pub const SERVER_TUNNEL_IP: &str = "198.19.0.1";
+const PEER_TUNNEL_IP: &str = "198.19.0.2";
+const TUNNEL_NETWORK: &str = "198.19.0.0/24";
+const TUNNEL_PREFIX: u8 = 24;
+const WIREGUARD_PORT: u16 = 51820;
+// MTU туннеля по умолчанию: MTU сети минус накладные расходы WireGuard поверх IPv6
+const WIREGUARD_OVERHEAD: u32 = 80;
+
+// Одна сторона стенда: пространство имен и конец пары veth в нем
+pub struct LabSide {
+    pub namespace: String,
+    veth: String,
+    underlay_ip: &'static str,
+    pub tunnel_ip: &'static str,
+}
+
+// Стенд из двух пространств имен, связанных парой veth, с туннелем WireGuard
+// поверх нее. Пространства имен удаляются при выходе из области видимости,
+// вместе с ними ядро удаляет и все интерфейсы стенда.
+pub struct LabTopology {
+    pub server: LabSide,
+    pub peer: LabSide,
+    created: Vec<String>,
+}
+
+impl LabTopology {
+    // Функция для создания стенда; при ошибке уже созданное удаляется
+    pub fn create(params: &LabParameters) -> Result<Self, AppError> {
+        let id = process::id();
+        let mut topology = LabTopology {
+            server: LabSide {
+                namespace: format!("wgmtu-{}-server", id),
+                veth: format!("wgm{}s", id),
+                underlay_ip: SERVER_UNDERLAY_IP,
+                tunnel_ip: SERVER_TUNNEL_IP,
+            },
+            peer: LabSide {
+                namespace: format!("wgmtu-{}-peer", id),
+                veth: format!("wgm{}p", id),
+                underlay_ip: PEER_UNDERLAY_IP,
+                tunnel_ip: PEER_TUNNEL_IP,
+            },
+            created: Vec::new(),
+        };
+
+        topology.build(params)?;
+        Ok(topology)
+    }
+
+    fn build(&mut self, params: &LabParameters) -> Result<(), AppError> {
+        // Пространства имен
+        for namespace in [self.server.namespace.clone(), self.peer.namespace.clone()] {
+            run("ip", &["netns", "add", &namespace])?;
+            self.created.push(namespace);
+        }
+        println!(
+            "Created network namespaces {} and {}",
+            self.server.namespace, self.peer.namespace
+        );
+
+        // Сеть под туннелем: пара veth с заданным MTU
+        run(
+            "ip",
+            &[
+                "link", "add", &self.server.veth, "netns", &self.server.namespace, "type", "veth",
+                "peer", "name", &self.peer.veth, "netns", &self.peer.namespace,
+            ],
+        )?;
+        for side in [&self.server, &self.peer] {
+            let address = format!("{}/{}", side.underlay_ip, UNDERLAY_PREFIX);
+            let mtu = params.underlay_mtu.to_string();
+            ip(side, &["link", "set", "lo", "up"])?;
+            ip(side, &["link", "set", &side.veth, "mtu", &mtu])?;
+            ip(side, &["addr", "add", &address, "dev", &side.veth])?;
+            ip(side, &["link", "set", &side.veth, "up"])?;
+            add_netem(side, params)?;
+        }
+        println!(
+            "Underlay: {} <-> {}, MTU {}",
+            SERVER_UNDERLAY_IP, PEER_UNDERLAY_IP, params.underlay_mtu
+        );
+
+        // Туннель WireGuard поверх veth
+        let server_key = generate_private_key()?;
+        let peer_key = generate_private_key()?;
+        let server_public = public_key(&server_key)?;
+        let peer_public = public_key(&peer_key)?;
+        let tunnel_mtu = params.underlay_mtu.saturating_sub(WIREGUARD_OVERHEAD);
+        add_wireguard(&self.server, &server_key, &peer_public, &self.peer, tunnel_mtu)?;
+        add_wireguard(&self.peer, &peer_key, &server_public, &self.server, tunnel_mtu)?;
+        println!(
+            "Tunnel: {} <-> {} on {}, MTU {}",
+            SERVER_TUNNEL_IP, PEER_TUNNEL_IP, TUNNEL_INTERFACE, tunnel_mtu
+        );
+
+        Ok(())
+    }
+}
+
+impl Drop for LabTopology {
+    fn drop(&mut self) {
+        for namespace in self.created.drain(..).rev() {
+            println!("Deleting network namespace {}", namespace);
+            if let Err(e) = run("ip", &["netns", "delete", &namespace]) {
+                eprintln!("Failed to delete network namespace {}: {}", namespace, e);
+            }
+        }
+    }
+}
+
+// Функция для выполнения команды ip внутри пространства имен стороны
+fn ip(side: &LabSide, args: &[&str]) -> Result<String, AppError> {
+    let mut full_args = vec!["-n", side.namespace.as_str()];
+    full_args.extend_from_slice(args);
+    run("ip", &full_args)
+}
+
+// Функция для добавления задержки и потерь на исходящем интерфейсе стороны
+fn add_netem(side: &LabSide, params: &LabParameters) -> Result<(), AppError> {
+    if params.delay_ms.is_none() && params.loss_percent.is_none() {
+        return Ok(());
+    }
+
+    let delay = params.delay_ms.map(|ms| format!("{}ms", ms));
+    let loss = params.loss_percent.map(|percent| format!("{}%", percent));
+    let mut args = vec![
+        "-n", side.namespace.as_str(), "qdisc", "add", "dev", side.veth.as_str(), "root", "netem",
+    ];
+    if let Some(delay) = &delay {
+        args.extend(["delay", delay.as_str()]);
+    }
+    if let Some(loss) = &loss {
+        args.extend(["loss", loss.as_str()]);
+    }
+
+    run("tc", &args).map(|_| ())
+}
+
+// Функция для создания интерфейса WireGuard стороны и настройки ее пира
+fn add_wireguard(
+    side: &LabSide,
+    private_key: &str,
+    remote_public_key: &str,
+    remote: &LabSide,
+    mtu: u32,
+) -> Result<(), AppError> {
+    let address = format!("{}/{}", side.tunnel_ip, TUNNEL_PREFIX);
+    let endpoint = format!("{}:{}", remote.underlay_ip, WIREGUARD_PORT);
+    let port = WIREGUARD_PORT.to_string();
+
+    ip(side, &["link", "add", TUNNEL_INTERFACE, "type", "wireguard"])?;
+    run_with_input(
+        "ip",
+        &[
+            "netns", "exec", &side.namespace, "wg", "set", TUNNEL_INTERFACE, "listen-port", &port,
+            "private-key", "/dev/stdin", "peer", remote_public_key, "allowed-ips", TUNNEL_NETWORK,
+            "endpoint", &endpoint,
+        ],
+        private_key,
+    )?;
+    ip(side, &["addr", "add", &address, "dev", TUNNEL_INTERFACE])?;
+    ip(side, &["link", "set", TUNNEL_INTERFACE, "mtu", &mtu.to_string()])?;
+    ip(side, &["link", "set", TUNNEL_INTERFACE, "up"])?;
+    Ok(())
+}
+
+// Функция для генерации закрытого ключа WireGuard
+fn generate_private_key() -> Result<String, AppError> {
+    run("wg", &["genkey"])
+}
+
+// Функция для получения открытого ключа WireGuard из закрытого
+fn public_key(private_key: &str) -> Result<String, AppError> {
+    run_with_input("wg", &["pubkey"], private_key)
+}
+
+// Функция для выполнения команды; возвращает ее вывод без пробелов по краям
+fn run(program: &str, args: &[&str]) -> Result<String, AppError> {
+    run_with_input(program, args, "")
+}
+
+// Функция для выполнения команды с данными на стандартном входе
+fn run_with_input(program: &str, args: &[&str], input: &str) -> Result<String, AppError> {
+    let command = format!("{} {}", program, args.join(" "));
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| AppError::Lab(format!("Failed to run {}: {}", program, e)))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(input.as_bytes())
+            .map_err(|e| AppError::Lab(format!("Failed to write to {}: {}", program, e)))?;
+    }
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| AppError::Lab(format!("Failed to run {}: {}", program, e)))?;
+    if !output.status.success() {
+        return Err(AppError::Lab(format!(
+            "'{}' failed: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
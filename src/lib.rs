@@ -0,0 +1,55 @@
+//! WireGuard MTU Finder as a library.
+//!
+//! The sweep is split between two hosts: [`run_server`] listens for a peer and
+//! switches the MTU of its interface on request, [`run_peer`] connects to it,
+//! measures throughput for every tested pair of MTU values and writes the
+//! results to a CSV file. The file can then be read back in-process with
+//! [`read_csv_data`], rendered with [`generate_heatmap`],
+//! scored with [`analyze`] to pick the MTU pair to deploy, or compared with
+//! another run by [`compare_runs`];
+//! [`set_interface_mtu`] writes the chosen value into a wg-quick config.
+//!
+//! Both sides are generic over how they reach the world: [`serve_sweep`] and
+//! [`run_peer_sweep`] take a [`ControlTransport`], an [`MtuController`] and,
+//! on the peer, a [`ThroughputTester`]. [`run_server`] and [`run_peer`] plug in
+//! TCP, netlink and the real throughput engines; [`memory_transport_pair`],
+//! [`MemoryLinks`] and [`MemoryThroughput`] run the whole protocol inside one
+//! process without root or a tunnel. Setting [`PeerParameters::simulation`]
+//! makes [`run_peer`] run both sides that way against a [`SimulatedNetwork`].
+//!
+//! While a sweep is running the original MTU of the interface is restored on
+//! return, and on SIGINT/SIGTERM the process exits after restoring it. With
+//! [`PeerParameters::apply_best`] both sides instead keep the recommended pair.
+
+pub mod analysis;
+pub mod data;
+pub mod error;
+pub mod heatmap;
+pub mod mtu_testing;
+mod network;
+mod utils;
+
+pub use crate::analysis::analyze;
+pub use crate::data::models::{
+    AnalysisObjective, AnalysisParameters, CompareParameters, DataPoint, HeatmapDelta,
+    HeatmapMetric, HeatmapParameters, HeatmapStatistic, LabParameters, MetricSummary,
+    MetricValues, MtuTestResult, PeerParameters, RunMetadata, ServerEndpoints, SimulationModel,
+    SweepConfig, SweepRequest, SweepStrategy, TestParameters, TestProtocol, ThroughputEngine,
+    ThroughputOptions, ThroughputStats,
+};
+pub use crate::error::AppError;
+pub use crate::heatmap::compare::compare_runs;
+pub use crate::heatmap::data_reader::read_csv_data;
+pub use crate::heatmap::error::HeatmapError;
+pub use crate::heatmap::generate_heatmap;
+pub use crate::mtu_testing::{run_peer, run_peer_sweep, run_server, serve_sweep};
+pub use crate::network::error::MtuError;
+pub use crate::network::memory::{
+    MemoryLinks, MemoryThroughput, MemoryTransport, memory_transport_pair,
+};
+pub use crate::network::messages::{ControlTransport, Message};
+pub use crate::network::mtu::{MtuController, SystemMtu};
+pub use crate::network::simulation::SimulatedNetwork;
+pub use crate::network::throughput::{SystemThroughput, ThroughputTester};
+pub use crate::utils::metadata::load_metadata;
+pub use crate::utils::wg_quick::set_interface_mtu;
@@ -1,15 +1,16 @@
 mod cli;
-mod data;
-mod heatmap;
-mod mtu_testing;
-mod network;
-mod utils;
+mod lab;
 
 use crate::cli::{Cli, Commands};
-use crate::heatmap::generate_heatmap;
-use crate::mtu_testing::{run_peer, run_server};
+use crate::lab::run_lab;
 use clap::Parser;
-use crate::data::models::{HeatmapParameters, PeerParameters, TestParameters};
+use std::process;
+use wg_mtu_finder::data::models::DEFAULT_SIM_INTERFACE;
+use wg_mtu_finder::{
+    AnalysisParameters, AppError, CompareParameters, HeatmapParameters, LabParameters,
+    PeerParameters, SimulationModel, SweepRequest, TestParameters, ThroughputOptions, analyze,
+    compare_runs, generate_heatmap, run_peer, run_server,
+};
 
 fn main() {
     // Парсим аргументы командной строки
@@ -23,46 +24,200 @@ fn main() {
             max_mtu,
             step,
             server_port,
+            bind,
+            tunnel_ip,
+            control_timeout,
+            heartbeat_interval,
             iperf_port,
+            engine,
+            strategy,
+            refine_step,
         } => {
-            run_server(TestParameters {
+            let result = run_server(TestParameters {
                 interface: interface.clone(),
                 min_mtu: *min_mtu,
                 max_mtu: *max_mtu,
                 step: *step,
                 control_port: *server_port,
+                bind_ip: *bind,
+                tunnel_ip: tunnel_ip.clone(),
+                control_timeout_secs: *control_timeout,
+                heartbeat_interval_secs: *heartbeat_interval,
                 iperf_port: *iperf_port,
+                engine: *engine,
+                strategy: *strategy,
+                refine_step: *refine_step,
             });
+            exit_on_error(result);
         }
         Commands::Peer {
             interface,
             server_ip,
+            control_ip,
             server_port,
+            control_timeout,
+            heartbeat_interval,
             iperf_port,
             min_mtu,
             max_mtu,
             step,
+            refine_step,
+            repeats,
             csv_file,
+            resume,
+            apply_best,
+            engine,
+            strategy,
+            protocol,
+            udp_bitrate,
+            udp_payload_size,
+            duration,
+            omit,
+            parallel,
+            window,
+            congestion,
+            simulate,
+            sim_underlay_mtu,
+            sim_overhead,
+            sim_bandwidth,
+            sim_fragmentation_penalty,
+            sim_noise,
+            sim_seed,
         } => {
-            run_peer(PeerParameters {
-                interface: interface.clone(),
+            let simulation = simulate.then(|| SimulationModel {
+                underlay_mtu: *sim_underlay_mtu,
+                overhead: *sim_overhead,
+                bandwidth_mbps: *sim_bandwidth,
+                fragmentation_penalty: *sim_fragmentation_penalty,
+                noise: *sim_noise,
+                seed: *sim_seed,
+            });
+            let result = run_peer(PeerParameters {
+                interface: interface
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_SIM_INTERFACE.to_string()),
                 server_ip: server_ip.clone(),
+                control_ip: control_ip.clone(),
                 control_port: *server_port,
-                iperf_port: *iperf_port,
+                control_timeout_secs: *control_timeout,
+                heartbeat_interval_secs: *heartbeat_interval,
+                repeats: *repeats,
+                csv_file: resume.clone().unwrap_or_else(|| csv_file.clone()),
+                resume: resume.is_some(),
+                apply_best: *apply_best,
+                requested: SweepRequest {
+                    min_mtu: *min_mtu,
+                    max_mtu: *max_mtu,
+                    step: *step,
+                    iperf_port: *iperf_port,
+                    engine: *engine,
+                    strategy: *strategy,
+                    refine_step: *refine_step,
+                },
+                throughput: ThroughputOptions {
+                    protocol: *protocol,
+                    udp_bitrate_mbps: *udp_bitrate,
+                    udp_payload_size: *udp_payload_size,
+                    duration_secs: *duration,
+                    omit_secs: *omit,
+                    parallel_streams: *parallel,
+                    window_bytes: *window,
+                    congestion: congestion.clone(),
+                },
+                simulation,
+            });
+            exit_on_error(result);
+        }
+        Commands::Lab {
+            underlay_mtu,
+            delay,
+            loss,
+            min_mtu,
+            max_mtu,
+            step,
+            engine,
+            strategy,
+            protocol,
+            duration,
+            repeats,
+            csv_file,
+        } => {
+            let result = run_lab(LabParameters {
+                underlay_mtu: *underlay_mtu,
+                delay_ms: *delay,
+                loss_percent: *loss,
                 min_mtu: *min_mtu,
                 max_mtu: *max_mtu,
                 step: *step,
+                engine: *engine,
+                strategy: *strategy,
+                protocol: *protocol,
+                duration_secs: *duration,
+                repeats: *repeats,
                 csv_file: csv_file.clone(),
             });
+            exit_on_error(result);
         }
         Commands::Heatmap {
             log_filepath,
             heatmap_filepath,
+            metric,
+            statistic,
+        } => {
+            let result = generate_heatmap(HeatmapParameters {
+                log_filepath: log_filepath.clone(),
+                heatmap_filepath: heatmap_filepath.clone(),
+                metric: *metric,
+                statistic: *statistic,
+            });
+            exit_on_error(result.map_err(AppError::from));
+        }
+        Commands::Analyze {
+            log_filepath,
+            objective,
+            upload_weight,
+            tolerance,
+            server_config,
+            peer_config,
+            config_dir,
+            config_diff,
         } => {
-            generate_heatmap(HeatmapParameters {
+            let result = analyze(AnalysisParameters {
                 log_filepath: log_filepath.clone(),
+                objective: *objective,
+                upload_weight: *upload_weight,
+                tolerance_percent: *tolerance,
+                server_config: server_config.clone(),
+                peer_config: peer_config.clone(),
+                config_output_dir: (!*config_diff).then(|| config_dir.clone()),
+            });
+            exit_on_error(result);
+        }
+        Commands::Compare {
+            baseline,
+            candidate,
+            heatmap_filepath,
+            metric,
+            delta,
+            top,
+        } => {
+            let result = compare_runs(CompareParameters {
+                baseline_filepath: baseline.clone(),
+                candidate_filepath: candidate.clone(),
                 heatmap_filepath: heatmap_filepath.clone(),
-            }).unwrap();
+                metric: *metric,
+                delta: *delta,
+                top: *top,
+            });
+            exit_on_error(result.map_err(AppError::from));
         }
     }
 }
+
+// Завершаем процесс с кодом, соответствующим категории ошибки
+fn exit_on_error(result: Result<(), AppError>) {
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        process::exit(e.exit_code());
+    }
+}
@@ -0,0 +1,81 @@
+use crate::data::models::SweepConfig;
+use crate::error::AppError;
+use crate::mtu_testing::peer::candidate_mtus;
+use crate::mtu_testing::session::ProbeSession;
+use crate::network::messages::ControlTransport;
+use crate::network::mtu::MtuController;
+use crate::network::throughput::ThroughputTester;
+
+// Функция для адаптивного перебора: грубая сетка с шагом step,
+// затем уточнение с шагом refine_step вокруг лучшей пары.
+pub fn run_adaptive_sweep<T: ControlTransport, M: MtuController, P: ThroughputTester>(
+    mut session: ProbeSession<T, M, P>,
+) -> Result<(), AppError> {
+    let config = session.config;
+
+    // Грубый проход
+    let coarse = candidate_mtus(config.min_mtu, config.max_mtu, config.step);
+    println!("Coarse pass over {} MTU values", coarse.len());
+    probe_grid(&mut session, &coarse, &coarse)?;
+
+    let (best_server, best_peer) = match session.best() {
+        Some((pair, score)) if score > 0.0 => {
+            println!(
+                "Coarse pass best: server {}, peer {} ({:.1} Mbps)",
+                pair.0, pair.1, score
+            );
+            pair
+        }
+        _ => {
+            println!("No successful tests in the coarse pass, skipping refinement");
+            session.finish()?;
+            return Ok(());
+        }
+    };
+
+    // Уточнение в окрестности лучшей пары
+    let server_fine = refine_range(config, best_server);
+    let peer_fine = refine_range(config, best_peer);
+    println!(
+        "Refining around best pair: server {}..{}, peer {}..{}, step {}",
+        server_fine.first().unwrap_or(&best_server),
+        server_fine.last().unwrap_or(&best_server),
+        peer_fine.first().unwrap_or(&best_peer),
+        peer_fine.last().unwrap_or(&best_peer),
+        config.refine_step
+    );
+    probe_grid(&mut session, &server_fine, &peer_fine)?;
+
+    let best = session.best();
+    let probed = session.scores.len();
+    session.finish()?;
+
+    if let Some(((server_mtu, peer_mtu), score)) = best {
+        println!(
+            "Best MTU pair found: server {}, peer {} ({:.1} Mbps, {} pairs probed)",
+            server_mtu, peer_mtu, score, probed
+        );
+    }
+    Ok(())
+}
+
+// Функция для измерения всех пар сетки
+fn probe_grid<T: ControlTransport, M: MtuController, P: ThroughputTester>(
+    session: &mut ProbeSession<T, M, P>,
+    server_mtus: &[u32],
+    peer_mtus: &[u32],
+) -> Result<(), AppError> {
+    for &server_mtu in server_mtus.iter().rev() {
+        for &peer_mtu in peer_mtus.iter().rev() {
+            session.probe(server_mtu, peer_mtu)?;
+        }
+    }
+    Ok(())
+}
+
+// Функция для получения значений MTU в пределах одного грубого шага от center
+fn refine_range(config: &SweepConfig, center: u32) -> Vec<u32> {
+    let low = center.saturating_sub(config.step).max(config.min_mtu);
+    let high = (center + config.step).min(config.max_mtu);
+    candidate_mtus(low, high, config.refine_step)
+}
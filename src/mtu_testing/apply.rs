@@ -0,0 +1,107 @@
+use crate::analysis::{PairScore, recommend, score_pairs};
+use crate::data::models::{
+    AnalysisObjective, DEFAULT_PLATEAU_TOLERANCE_PERCENT, DEFAULT_UPLOAD_WEIGHT, PeerParameters,
+    TestParameters,
+};
+use crate::error::AppError;
+use crate::heatmap::data_reader::read_csv_data;
+use crate::network::error::MtuError;
+use crate::network::messages::{
+    ControlTransport, Message, receive_control_message, report_error, send_message,
+    unexpected_message,
+};
+use crate::network::mtu::{MtuController, get_remote_mtu};
+
+// Функция для выбора лучшей пары по всем результатам перебора и установки ее
+// MTU на обеих сторонах. Пара выбирается так же, как в analyze с настройками
+// по умолчанию. Сначала MTU меняет пир: если сервер не сможет применить свой,
+// пир вернет исходный при выходе.
+pub(super) fn apply_best_pair<T: ControlTransport, M: MtuController>(
+    params: &PeerParameters,
+    stream: &mut T,
+    mtu: &M,
+) -> Result<(u32, u32), AppError> {
+    let pick = match best_pair(&params.csv_file) {
+        Ok(pick) => pick,
+        Err(e) => {
+            report_error(stream, &format!("Peer could not pick the best MTU pair: {}", e));
+            return Err(e);
+        }
+    };
+    let server_mtu = u32::from(pick.server_mtu);
+    let peer_mtu = u32::from(pick.peer_mtu);
+    println!(
+        "Applying best MTU pair: server MTU {}, peer MTU {} ({:.2} Mbps)",
+        server_mtu, peer_mtu, pick.score
+    );
+
+    if let Err(e) = set_and_verify(mtu, &params.interface, peer_mtu) {
+        report_error(stream, &format!("Failed to apply peer MTU {}: {}", peer_mtu, e));
+        return Err(e);
+    }
+
+    send_message(stream, Message::ApplyMtu(server_mtu))?;
+    let applied = get_remote_mtu(stream)
+        .map_err(|e| e.context(format!("while applying server MTU {}", server_mtu)))?;
+    if applied != server_mtu {
+        return Err(AppError::Protocol(format!(
+            "Server confirmed MTU {}, expected {}",
+            applied, server_mtu
+        )));
+    }
+
+    println!(
+        "Best MTU pair applied: server MTU {}, peer MTU {}",
+        server_mtu, peer_mtu
+    );
+    Ok((server_mtu, peer_mtu))
+}
+
+// Функция для установки MTU, который пир выбрал по результатам перебора
+pub(super) fn apply_requested_mtu<T: ControlTransport, M: MtuController>(
+    params: &TestParameters,
+    stream: &mut T,
+    mtu: &M,
+) -> Result<u32, AppError> {
+    let requested = match receive_control_message(stream, "Peer")? {
+        Message::ApplyMtu(requested) => requested,
+        other => return Err(unexpected_message(stream, "peer", "ApplyMtu", &other)),
+    };
+    println!("Peer requested best server MTU: {}", requested);
+
+    if let Err(e) = set_and_verify(mtu, &params.interface, requested) {
+        report_error(stream, &format!("Failed to apply server MTU {}: {}", requested, e));
+        return Err(e);
+    }
+
+    // Подтверждаем примененное значение
+    send_message(stream, Message::MtuValue(requested))?;
+    println!("Best MTU {} applied on interface {}", requested, params.interface);
+    Ok(requested)
+}
+
+// Функция для выбора рекомендуемой пары по CSV файлу перебора
+fn best_pair(csv_file: &str) -> Result<PairScore, AppError> {
+    let data = read_csv_data(csv_file)
+        .map_err(|e| AppError::Analysis(format!("Failed to read {}: {}", csv_file, e)))?;
+    let scores = score_pairs(&data, AnalysisObjective::Min, DEFAULT_UPLOAD_WEIGHT);
+
+    recommend(&scores, DEFAULT_PLATEAU_TOLERANCE_PERCENT)
+        .map(|recommendation| recommendation.plateau.pick)
+        .ok_or_else(|| AppError::Analysis("No results to pick the best MTU pair from".to_string()))
+}
+
+// Функция для установки MTU с проверкой, что интерфейс действительно его использует
+fn set_and_verify<M: MtuController>(mtu: &M, interface: &str, value: u32) -> Result<(), AppError> {
+    mtu.set_mtu(interface, value)?;
+
+    let actual = mtu.get_mtu(interface)?;
+    if actual != value {
+        return Err(AppError::from(MtuError::NotApplied {
+            interface: interface.to_string(),
+            expected: value,
+            actual,
+        }));
+    }
+    Ok(())
+}
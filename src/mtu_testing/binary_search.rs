@@ -0,0 +1,89 @@
+use crate::error::AppError;
+use crate::mtu_testing::peer::candidate_mtus;
+use crate::mtu_testing::session::ProbeSession;
+use crate::network::messages::ControlTransport;
+use crate::network::mtu::MtuController;
+use crate::network::throughput::ThroughputTester;
+
+// Доля от базовой скорости, ниже которой считаем, что скорость обвалилась
+const COLLAPSE_RATIO: f64 = 0.5;
+
+// Функция для поиска наибольшей пары MTU делением пополам.
+// Сначала ищется MTU сервера при минимальном MTU пира, затем MTU пира
+// при найденном MTU сервера.
+pub fn run_binary_search<T: ControlTransport, M: MtuController, P: ThroughputTester>(
+    mut session: ProbeSession<T, M, P>,
+) -> Result<(), AppError> {
+    let config = session.config;
+    let candidates = candidate_mtus(config.min_mtu, config.max_mtu, config.step);
+
+    let Some(&lowest) = candidates.first() else {
+        println!("No MTU values to test in the given range");
+        session.finish()?;
+        return Ok(());
+    };
+
+    // Базовая скорость при минимальных MTU с обеих сторон
+    let baseline = session.probe(lowest, lowest)?;
+    if baseline <= 0.0 {
+        println!("Baseline test at MTU {} failed, aborting search", lowest);
+        session.finish()?;
+        return Ok(());
+    }
+    let threshold = baseline * COLLAPSE_RATIO;
+    println!(
+        "Baseline throughput: {:.1} Mbps, collapse threshold: {:.1} Mbps",
+        baseline, threshold
+    );
+
+    let server_idx = largest_good(&mut session, &candidates, threshold, |session, mtu| {
+        session.probe(mtu, lowest)
+    })?;
+    let best_server = candidates[server_idx];
+    println!("Largest stable server MTU: {}", best_server);
+
+    let peer_idx = largest_good(&mut session, &candidates, threshold, |session, mtu| {
+        session.probe(best_server, mtu)
+    })?;
+    let best_peer = candidates[peer_idx];
+    let probed = session.scores.len();
+
+    session.finish()?;
+
+    println!(
+        "Best MTU pair found: server {}, peer {} ({} pairs probed)",
+        best_server, best_peer, probed
+    );
+    Ok(())
+}
+
+// Функция для поиска индекса наибольшего MTU, при котором скорость не ниже порога.
+// Нулевой индекс считается заведомо рабочим.
+fn largest_good<T: ControlTransport, M: MtuController, P: ThroughputTester>(
+    session: &mut ProbeSession<T, M, P>,
+    candidates: &[u32],
+    threshold: f64,
+    mut probe: impl FnMut(&mut ProbeSession<T, M, P>, u32) -> Result<f64, AppError>,
+) -> Result<usize, AppError> {
+    let mut is_good = |session: &mut ProbeSession<T, M, P>, idx: usize| {
+        probe(session, candidates[idx]).map(|score| score >= threshold)
+    };
+
+    let mut low = 0;
+    let mut high = candidates.len() - 1;
+
+    if is_good(session, high)? {
+        return Ok(high);
+    }
+
+    while high - low > 1 {
+        let mid = low + (high - low) / 2;
+        if is_good(session, mid)? {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    Ok(low)
+}
@@ -0,0 +1,171 @@
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::net::TcpStream;
+use std::time::Duration;
+use crate::data::models::{ServerEndpoints, SweepConfig, SweepRequest};
+use crate::error::AppError;
+use crate::network::messages::{
+    ControlTransport, Heartbeat, Message, PROTOCOL_VERSION, receive_control_message, report_error,
+    send_message, set_control_timeouts, start_heartbeat, unexpected_message,
+};
+
+// Функция для настройки таймаутов управляющего соединения и запуска Heartbeat.
+// Heartbeat работает, пока возвращенное значение не будет удалено.
+pub(super) fn start_keepalive(
+    stream: &TcpStream,
+    timeout_secs: u64,
+    heartbeat_interval_secs: u64,
+) -> Result<Heartbeat, AppError> {
+    if heartbeat_interval_secs == 0 || heartbeat_interval_secs >= timeout_secs {
+        return Err(AppError::Config(format!(
+            "--heartbeat-interval ({}s) must be positive and shorter than --control-timeout ({}s)",
+            heartbeat_interval_secs, timeout_secs
+        )));
+    }
+
+    set_control_timeouts(stream, Duration::from_secs(timeout_secs)).map_err(|e| {
+        AppError::Connection(format!("Failed to set control connection timeouts: {}", e))
+    })?;
+    start_heartbeat(stream, Duration::from_secs(heartbeat_interval_secs))
+        .map_err(|e| AppError::Connection(format!("Failed to start heartbeat: {}", e)))
+}
+
+// Функция для согласования параметров на стороне сервера:
+// проверяем версию протокола, объявляем параметры перебора и ждем, что пир их примет.
+// Возвращает, попросил ли пир применить лучший MTU после перебора.
+pub(super) fn announce_config<T: ControlTransport>(
+    stream: &mut T,
+    config: &SweepConfig,
+    current_mtu: Option<u32>,
+    endpoints: &ServerEndpoints,
+) -> Result<bool, AppError> {
+    match receive_control_message(stream, "Peer")? {
+        Message::Hello { version } if version == PROTOCOL_VERSION => {}
+        Message::Hello { version } => {
+            let reason = format!(
+                "Protocol version mismatch: server speaks {}, peer speaks {}",
+                PROTOCOL_VERSION, version
+            );
+            report_error(stream, &reason);
+            return Err(AppError::Protocol(reason));
+        }
+        other => return Err(unexpected_message(stream, "peer", "Hello", &other)),
+    }
+
+    send_message(
+        stream,
+        Message::Config {
+            config: config.clone(),
+            current_mtu,
+            endpoints: endpoints.clone(),
+        },
+    )?;
+
+    match receive_control_message(stream, "Peer")? {
+        Message::ConfigAccepted { apply_best } => Ok(apply_best),
+        other => Err(unexpected_message(stream, "peer", "ConfigAccepted", &other)),
+    }
+}
+
+// Функция для согласования параметров на стороне пира.
+// check — дополнительная проверка пира до того, как он примет параметры.
+// Возвращает параметры сервера, MTU его интерфейса на момент запуска и его адреса.
+pub(super) fn negotiate_config<T: ControlTransport>(
+    stream: &mut T,
+    requested: &SweepRequest,
+    apply_best: bool,
+    check: impl FnOnce(&SweepConfig) -> Result<(), String>,
+) -> Result<(SweepConfig, Option<u32>, ServerEndpoints), AppError> {
+    send_message(
+        stream,
+        Message::Hello {
+            version: PROTOCOL_VERSION,
+        },
+    )?;
+
+    let (config, current_mtu, endpoints) = match receive_control_message(stream, "Server")? {
+        Message::Config {
+            config,
+            current_mtu,
+            endpoints,
+        } => (config, current_mtu, endpoints),
+        other => return Err(unexpected_message(stream, "server", "Config", &other)),
+    };
+
+    // Сервер проверяет диапазон при запуске, но пир не полагается на это
+    let range = check_sweep_range(config.min_mtu, config.max_mtu, config.step, config.refine_step);
+    if let Err(reason) = range {
+        let reason = format!("Server announced invalid sweep parameters: {}", reason);
+        report_error(stream, &reason);
+        return Err(AppError::Config(reason));
+    }
+
+    let mut mismatches = Vec::new();
+    check_requested(&mut mismatches, "min-mtu", requested.min_mtu, config.min_mtu);
+    check_requested(&mut mismatches, "max-mtu", requested.max_mtu, config.max_mtu);
+    check_requested(&mut mismatches, "step", requested.step, config.step);
+    check_requested(&mut mismatches, "iperf-port", requested.iperf_port, config.iperf_port);
+    check_requested(&mut mismatches, "engine", requested.engine, config.engine);
+    check_requested(&mut mismatches, "strategy", requested.strategy, config.strategy);
+    check_requested(&mut mismatches, "refine-step", requested.refine_step, config.refine_step);
+
+    if !mismatches.is_empty() {
+        let reason = format!("Parameter mismatch with server: {}", mismatches.join(", "));
+        report_error(stream, &reason);
+        return Err(AppError::Config(reason));
+    }
+
+    if let Err(reason) = check(&config) {
+        report_error(stream, &reason);
+        return Err(AppError::Config(reason));
+    }
+
+    send_message(stream, Message::ConfigAccepted { apply_best })?;
+    Ok((config, current_mtu, endpoints))
+}
+
+// Функция для проверки диапазона и шага перебора. Возвращает описание ошибки.
+pub(super) fn check_sweep_range(
+    min_mtu: u32,
+    max_mtu: u32,
+    step: u32,
+    refine_step: u32,
+) -> Result<(), String> {
+    if step == 0 || refine_step == 0 {
+        return Err("--step and --refine-step must be at least 1".to_string());
+    }
+    if min_mtu > max_mtu {
+        return Err(format!(
+            "--min-mtu ({}) must not be greater than --max-mtu ({})",
+            min_mtu, max_mtu
+        ));
+    }
+    Ok(())
+}
+
+// Функция для получения от пира списка уже измеренных пар
+pub(super) fn receive_completed_pairs<T: ControlTransport>(
+    stream: &mut T,
+) -> Result<HashSet<(u32, u32)>, AppError> {
+    match receive_control_message(stream, "Peer")? {
+        Message::CompletedPairs(pairs) => Ok(pairs.into_iter().collect()),
+        other => Err(unexpected_message(stream, "peer", "CompletedPairs", &other)),
+    }
+}
+
+// Функция для сравнения явно заданного пиром значения со значением сервера
+fn check_requested<T: PartialEq + Debug>(
+    mismatches: &mut Vec<String>,
+    name: &str,
+    requested: Option<T>,
+    announced: T,
+) {
+    if let Some(requested) = requested
+        && requested != announced
+    {
+        mismatches.push(format!(
+            "--{} is {:?} on peer but {:?} on server",
+            name, requested, announced
+        ));
+    }
+}
@@ -1,5 +1,14 @@
+mod adaptive;
+mod apply;
+mod binary_search;
+mod handshake;
 mod peer;
+mod restore;
 mod server;
+mod session;
+mod simulation;
+#[cfg(test)]
+mod tests;
 
-pub use peer::run_peer;
-pub use server::run_server;
+pub use peer::{run_peer, run_peer_sweep};
+pub use server::{run_server, serve_sweep};
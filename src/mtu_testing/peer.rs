@@ -1,132 +1,547 @@
+use std::fs::File;
 use std::net::TcpStream;
-use crate::data::models::{MtuTestResult, PeerParameters};
-use crate::network::messages::{Message, send_message, receive_message};
-use crate::network::iperf::{check_iperf_installed, run_iperf_test};
-use crate::network::mtu::{get_remote_mtu, set_mtu};
-use crate::utils::csv_utils::{create_csv_file, save_result_to_csv};
-
-pub fn run_peer(params: PeerParameters) {
-    // Проверяем наличие iperf3
-    if !check_iperf_installed() {
-        eprintln!("Error: iperf3 is not installed or not in PATH");
-        return;
-    }
-
-    // Создаем CSV файл для результатов
-    let mut writer = create_csv_file(&params.csv_file);
-
-    // Подключаемся к серверу
-    println!("Connecting to server {}:{}", params.server_ip, params.control_port);
-    let addr = format!("{}:{}", params.server_ip, params.control_port);
-    let mut stream = match TcpStream::connect(&addr) {
-        Ok(stream) => stream,
+use csv::Writer;
+use chrono::Local;
+use crate::data::models::{
+    MtuTestResult, PeerParameters, RunMetadata, ServerEndpoints, SweepConfig, SweepStrategy,
+    ThroughputStats,
+};
+use crate::error::AppError;
+use crate::mtu_testing::apply::apply_best_pair;
+use crate::mtu_testing::adaptive::run_adaptive_sweep;
+use crate::mtu_testing::binary_search::run_binary_search;
+use crate::heatmap::data_reader::read_csv_data;
+use crate::mtu_testing::handshake::{negotiate_config, start_keepalive};
+use crate::mtu_testing::session::{PairScores, ProbeSession};
+use crate::mtu_testing::simulation::run_simulation;
+use crate::mtu_testing::restore::{MtuRestoreGuard, register_control_stream};
+use crate::network::messages::{
+    ControlTransport, Message, receive_control_message, report_error, send_message,
+    unexpected_message,
+};
+use crate::network::mtu::{MtuController, SystemMtu, get_remote_mtu};
+use crate::network::throughput::{SystemThroughput, ThroughputTester};
+use crate::utils::csv_utils::{append_csv_file, create_csv_file, save_result_to_csv};
+use crate::utils::metadata::{load_metadata, save_metadata};
+use crate::utils::stats::summarize;
+
+pub fn run_peer(params: PeerParameters) -> Result<(), AppError> {
+    // В режиме симуляции сервер и сеть заменяет модель
+    if let Some(model) = &params.simulation {
+        return run_simulation(&params, model);
+    }
+
+    // Запоминаем исходный MTU, чтобы вернуть его при выходе
+    let mtu_guard = MtuRestoreGuard::new(&params.interface);
+
+    // Подключаемся к серверу, управляющее соединение может идти мимо туннеля
+    let control_ip = control_address(&params).ok_or_else(|| {
+        AppError::Config("--server-ip or --control-ip is required".to_string())
+    })?;
+    println!("Connecting to server {}:{}", control_ip, params.control_port);
+    let addr = format!("{}:{}", control_ip, params.control_port);
+    let mut stream = TcpStream::connect(&addr)
+        .map_err(|e| AppError::Connection(format!("Failed to connect to server {}: {}", addr, e)))?;
+    register_control_stream(&stream);
+    let _heartbeat = start_keepalive(
+        &stream,
+        params.control_timeout_secs,
+        params.heartbeat_interval_secs,
+    )?;
+
+    let applied = run_peer_sweep(&params, &mut stream, &SystemMtu, &SystemThroughput)?;
+
+    // Лучший MTU, примененный по --apply-best, остается на интерфейсе
+    if applied.is_some()
+        && let Some(guard) = mtu_guard
+    {
+        guard.keep_current();
+    }
+    Ok(())
+}
+
+// Функция для проведения перебора через уже подключенное управляющее соединение.
+// MTU интерфейса меняется через mtu, скорость измеряется через tester.
+// Возвращает пару (server_mtu, peer_mtu), примененную по --apply-best.
+pub fn run_peer_sweep<T: ControlTransport, M: MtuController, P: ThroughputTester>(
+    params: &PeerParameters,
+    stream: &mut T,
+    mtu: &M,
+    tester: &P,
+) -> Result<Option<(u32, u32)>, AppError> {
+    let current_mtu = mtu.get_mtu(&params.interface).ok();
+
+    // Метаданные прерванного перебора: продолжать его можно только с теми же условиями
+    let resumed = if params.resume { load_metadata(&params.csv_file) } else { None };
+
+    // Создаем CSV файл для результатов или продолжаем прерванный перебор
+    let (mut writer, completed) = match open_results(params) {
+        Ok(results) => results,
         Err(e) => {
-            println!("Failed to connect to server: {}", e);
-            return;
+            report_error(stream, &format!("Peer failed to open results: {}", e));
+            return Err(e);
+        }
+    };
+
+    // Согласуем параметры перебора и получаем текущий MTU сервера
+    let (config, server_current_mtu, endpoints) =
+        negotiate_config(stream, &params.requested, params.apply_best, |config| {
+            resumed
+                .as_ref()
+                .map_or(Ok(()), |saved| check_resumed_run(params, saved, config))
+        })?;
+    println!("Using server configuration: {:?}", config);
+    match server_current_mtu {
+        Some(mtu) => println!("Current MTU of server interface: {}", mtu),
+        None => println!("Current MTU of server interface is unknown"),
+    }
+    let server_ip = match resolve_server_ip(params, &endpoints) {
+        Ok(server_ip) => server_ip,
+        Err(e) => {
+            report_error(stream, &format!("Peer rejected server endpoints: {}", e));
+            return Err(e);
+        }
+    };
+    let params = &PeerParameters {
+        server_ip: Some(server_ip),
+        ..params.clone()
+    };
+
+    // Проверяем наличие выбранного движка
+    if let Err(e) = tester.check_engine(config.engine) {
+        report_error(stream, &format!("{} on peer", e));
+        return Err(e);
+    }
+
+    // Сообщаем серверу, какие пары уже измерены
+    let mut pairs: Vec<(u32, u32)> = completed.keys().copied().collect();
+    pairs.sort_unstable();
+    send_message(stream, Message::CompletedPairs(pairs))?;
+
+    // Сохраняем метаданные запуска рядом с результатами. При продолжении
+    // оставляем исходные: MTU интерфейсов мог остаться от прерванного перебора.
+    if resumed.is_none() {
+        save_metadata(
+            &params.csv_file,
+            &RunMetadata {
+                started_at: Local::now().to_rfc3339(),
+                interface: params.interface.clone(),
+                server_ip: target_ip(params).to_string(),
+                control_ip: params.control_ip.clone(),
+                server_current_mtu,
+                peer_current_mtu: current_mtu,
+                min_mtu: config.min_mtu,
+                max_mtu: config.max_mtu,
+                step: config.step,
+                engine: config.engine,
+                strategy: config.strategy,
+                repeats: params.repeats,
+                throughput: params.throughput.clone(),
+                simulation: params.simulation.clone(),
+            },
+        )?;
+    }
+
+    match config.strategy {
+        SweepStrategy::Grid => {
+            run_grid_sweep(params, &config, stream, mtu, tester, &mut writer, &completed)?
+        }
+        SweepStrategy::Binary => run_binary_search(ProbeSession::new(
+            params,
+            &config,
+            stream,
+            mtu,
+            tester,
+            &mut writer,
+            completed,
+        ))?,
+        SweepStrategy::Adaptive => run_adaptive_sweep(ProbeSession::new(
+            params,
+            &config,
+            stream,
+            mtu,
+            tester,
+            &mut writer,
+            completed,
+        ))?,
+    }
+
+    // Завершение и сохранение результатов
+    println!("Results saved to {}", params.csv_file);
+
+    if !params.apply_best {
+        return Ok(None);
+    }
+    apply_best_pair(params, stream, mtu).map(Some)
+}
+
+// Функция для создания CSV файла результатов или открытия файла прерванного
+// перебора вместе с уже измеренными в нем парами
+fn open_results(params: &PeerParameters) -> Result<(Writer<File>, PairScores), AppError> {
+    if !params.resume {
+        return Ok((create_csv_file(&params.csv_file)?, PairScores::new()));
+    }
+
+    let completed = load_completed_pairs(&params.csv_file)?;
+    println!(
+        "Resuming sweep from {}: {} pairs already tested",
+        params.csv_file,
+        completed.len()
+    );
+    Ok((append_csv_file(&params.csv_file)?, completed))
+}
+
+// Функция для сравнения условий прерванного перебора с текущими: новые строки
+// CSV должны быть измерены так же, как уже записанные
+fn check_resumed_run(
+    params: &PeerParameters,
+    saved: &RunMetadata,
+    config: &SweepConfig,
+) -> Result<(), String> {
+    let (old, new) = (&saved.throughput, &params.throughput);
+    let fields = [
+        ("min-mtu", saved.min_mtu.to_string(), config.min_mtu.to_string()),
+        ("max-mtu", saved.max_mtu.to_string(), config.max_mtu.to_string()),
+        ("step", saved.step.to_string(), config.step.to_string()),
+        ("engine", format!("{:?}", saved.engine), format!("{:?}", config.engine)),
+        ("strategy", format!("{:?}", saved.strategy), format!("{:?}", config.strategy)),
+        ("repeats", saved.repeats.to_string(), params.repeats.to_string()),
+        ("protocol", format!("{:?}", old.protocol), format!("{:?}", new.protocol)),
+        ("udp-bitrate", old.udp_bitrate_mbps.to_string(), new.udp_bitrate_mbps.to_string()),
+        ("udp-payload-size", old.udp_payload_size.to_string(), new.udp_payload_size.to_string()),
+        ("duration", old.duration_secs.to_string(), new.duration_secs.to_string()),
+        ("omit", old.omit_secs.to_string(), new.omit_secs.to_string()),
+        ("parallel", old.parallel_streams.to_string(), new.parallel_streams.to_string()),
+        ("window", format!("{:?}", old.window_bytes), format!("{:?}", new.window_bytes)),
+        ("congestion", format!("{:?}", old.congestion), format!("{:?}", new.congestion)),
+    ];
+
+    let changed: Vec<String> = fields
+        .iter()
+        .filter(|(_, old, new)| old != new)
+        .map(|(name, old, new)| format!("--{} was {} but is now {}", name, old, new))
+        .collect();
+    if changed.is_empty() {
+        return Ok(());
+    }
+    Err(format!(
+        "Cannot resume {} with different settings: {}",
+        params.csv_file,
+        changed.join(", ")
+    ))
+}
+
+// Функция для выбора адреса сервера для замеров скорости: по умолчанию —
+// адрес в туннеле, объявленный сервером. Явно заданный --server-ip должен
+// с ним совпадать, иначе замеры шли бы не через туннель.
+fn resolve_server_ip(
+    params: &PeerParameters,
+    endpoints: &ServerEndpoints,
+) -> Result<String, AppError> {
+    println!(
+        "Server endpoints: control {}, tunnel {}",
+        endpoints.control_ip,
+        endpoints.tunnel_ip.as_deref().unwrap_or("not announced")
+    );
+
+    let server_ip = match (&params.server_ip, &endpoints.tunnel_ip) {
+        (Some(server_ip), Some(tunnel_ip)) if server_ip != tunnel_ip => {
+            return Err(AppError::Config(format!(
+                "--server-ip is {}, but server announces tunnel address {}",
+                server_ip, tunnel_ip
+            )));
+        }
+        (Some(server_ip), _) => server_ip.clone(),
+        (None, Some(tunnel_ip)) => {
+            println!("Throughput tests target the announced tunnel address {}", tunnel_ip);
+            tunnel_ip.clone()
+        }
+        (None, None) => {
+            return Err(AppError::Config(
+                "--server-ip is required, server does not announce its tunnel address (--tunnel-ip)"
+                    .to_string(),
+            ));
         }
     };
 
+    // В симуляции туннеля нет, и управляющему соединению ничего не грозит
+    let through_tunnel = control_address(params) == Some(server_ip.as_str());
+    if through_tunnel && endpoints.tunnel_ip.is_some() && params.simulation.is_none() {
+        println!(
+            "Warning: control connection runs through the tunnel, use --control-ip to move it out of band"
+        );
+    }
+    Ok(server_ip)
+}
+
+// Адрес управляющего соединения: --control-ip, иначе --server-ip
+fn control_address(params: &PeerParameters) -> Option<&str> {
+    params.control_ip.as_deref().or(params.server_ip.as_deref())
+}
+
+// Адрес сервера для замеров скорости, выбранный при согласовании
+fn target_ip(params: &PeerParameters) -> &str {
+    params.server_ip.as_deref().unwrap_or_default()
+}
+
+// Функция для полного перебора: значения MTU сервера выбирает сервер
+fn run_grid_sweep<T: ControlTransport, M: MtuController, P: ThroughputTester>(
+    params: &PeerParameters,
+    config: &SweepConfig,
+    stream: &mut T,
+    mtu: &M,
+    tester: &P,
+    writer: &mut Writer<File>,
+    completed: &PairScores,
+) -> Result<(), AppError> {
+    let mut last_server_mtu = None;
+
     // Основной цикл тестирования
     loop {
         // Получаем сообщение о готовности сервера
-        match receive_message::<Message>(&mut stream) {
-            Ok(Message::ServerReady) => {
+        let message = receive_control_message(stream, "Server").map_err(|e| {
+            match last_server_mtu {
+                Some(mtu) => e.context(format!("after tests with server MTU {}", mtu)),
+                None => e,
+            }
+        })?;
+        match message {
+            Message::ServerReady => {
                 println!("Server is ready for testing");
-            },
-            Ok(Message::Finish) => {
+            }
+            Message::Finish => {
                 println!("Server signals testing is complete");
-                break;
-            },
-            Ok(_) => {
-                println!("Unexpected message from server");
-                break;
-            },
-            Err(e) => {
-                println!("Error receiving message from server: {}", e);
-                break;
+                return Ok(());
+            }
+            other => {
+                return Err(unexpected_message(stream, "server", "ServerReady or Finish", &other));
             }
         }
 
         // Получаем текущий MTU сервера
-        let server_mtu = match get_remote_mtu(&mut stream, &params.interface) {
-            Ok(mtu) => {
-                println!("Server MTU: {}", mtu);
-                mtu
-            },
-            Err(e) => {
-                println!("Could not get server MTU: {}, using default: {}", e, params.max_mtu);
-                params.max_mtu
-            }
-        };
+        let server_mtu = get_remote_mtu(stream)?;
+        println!("Server MTU: {}", server_mtu);
+        last_server_mtu = Some(server_mtu);
 
         // Тестирование с разными MTU на стороне клиента
-        run_client_side_tests(
-            &params,
-            server_mtu,
-            &mut writer,
-        );
+        let tests =
+            run_client_side_tests(params, config, stream, mtu, tester, server_mtu, writer, completed);
+        if let Err(e) = tests {
+            report_error(stream, &e.to_string());
+            return Err(e.context(format!("while testing with server MTU {}", server_mtu)));
+        }
 
         // Сообщаем серверу о завершении цикла тестов
-        send_message(&mut stream, Message::PeerDone);
+        send_message(stream, Message::PeerDone)?;
         println!("Sent PeerDone message to server");
     }
-
-    // Завершение и сохранение результатов
-    println!("Results saved to {}", params.csv_file);
 }
 
 // Функция для запуска клиентских тестов с разными MTU
-fn run_client_side_tests(
+#[allow(clippy::too_many_arguments)]
+fn run_client_side_tests<T: ControlTransport, M: MtuController, P: ThroughputTester>(
+    params: &PeerParameters,
+    config: &SweepConfig,
+    stream: &mut T,
+    mtu: &M,
+    tester: &P,
+    server_mtu: u32,
+    writer: &mut Writer<File>,
+    completed: &PairScores,
+) -> Result<(), AppError> {
+    // От большего MTU к меньшему
+    for &client_mtu in candidate_mtus(config.min_mtu, config.max_mtu, config.step).iter().rev() {
+        if completed.contains_key(&(server_mtu, client_mtu)) {
+            println!("Skipping client MTU {}, already tested", client_mtu);
+        } else {
+            measure_pair(params, config, stream, mtu, tester, server_mtu, client_mtu, writer)?;
+        }
+    }
+    Ok(())
+}
+
+// Функция для измерения скорости при заданной паре MTU и сохранения результата.
+// Неудачный замер скорости дает None, а ошибки установки MTU и записи
+// результата прерывают перебор.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn measure_pair<T: ControlTransport, M: MtuController, P: ThroughputTester>(
     params: &PeerParameters,
+    config: &SweepConfig,
+    stream: &mut T,
+    mtu: &M,
+    tester: &P,
     server_mtu: u32,
-    writer: &mut csv::Writer<std::fs::File>,
-) {
-    let mut client_mtu = params.max_mtu;
-
-    while client_mtu >= params.min_mtu {
-        println!("Testing with client MTU: {}", client_mtu);
-
-        // Установить MTU на интерфейсе
-        set_mtu(&params.interface, client_mtu);
-
-        // Выполнить тесты скорости
-        let test_results = run_speed_tests(&params.server_ip, params.iperf_port);
-
-        // Сохранить результаты, если они получены
-        if let Some((upload, download)) = test_results {
-            let result = MtuTestResult {
-                server_mtu,
-                client_mtu,
-                upload_rcv_mbps: upload.0,
-                upload_send_mbps: upload.1,
-                download_rcv_mbps: download.0,
-                download_send_mbps: download.1,
-            };
-
-            // Вывод результатов
-            println!("Results: {:?}", &result);
-
-            // Сохранение в CSV
-            save_result_to_csv(writer, &result);
+    client_mtu: u32,
+    writer: &mut Writer<File>,
+) -> Result<Option<MtuTestResult>, AppError> {
+    println!("Testing with client MTU: {}", client_mtu);
+
+    // Установить MTU на интерфейсе
+    mtu.set_mtu(&params.interface, client_mtu)?;
+
+    // Сообщаем серверу, какая пара сейчас измеряется
+    send_message(stream, Message::PeerMtu(client_mtu))?;
+
+    // Выполнить тесты скорости нужное число раз, неудачные прогоны пропускаются
+    let mut runs = Vec::new();
+    for repeat in 1..=params.repeats {
+        if params.repeats > 1 {
+            println!("Run {} of {}", repeat, params.repeats);
+        }
+        if let Some(run) = run_speed_tests(params, config, tester) {
+            runs.push(run);
         }
+    }
+    if runs.is_empty() {
+        return Ok(None);
+    }
+
+    let result = summarize_runs(server_mtu, client_mtu, &runs);
 
-        // Уменьшить MTU для следующего цикла
-        client_mtu -= params.step;
+    // Вывод результатов
+    println!("Results: {:?}", &result);
+
+    // Сохранение в CSV
+    save_result_to_csv(writer, &result)?;
+
+    Ok(Some(result))
+}
+
+// Функция для сведения повторных прогонов в один результат
+fn summarize_runs(
+    server_mtu: u32,
+    client_mtu: u32,
+    runs: &[(ThroughputStats, ThroughputStats)],
+) -> MtuTestResult {
+    let summary = |metric: fn(&(ThroughputStats, ThroughputStats)) -> f64| {
+        summarize(&runs.iter().map(metric).collect::<Vec<_>>())
+    };
+    let mean_count = |count: fn(&(ThroughputStats, ThroughputStats)) -> u64| {
+        (runs.iter().map(count).sum::<u64>() as f64 / runs.len() as f64).round() as u64
+    };
+
+    let upload_rcv_stats = summary(|(upload, _)| upload.rcv_mbps);
+    let upload_send_stats = summary(|(upload, _)| upload.send_mbps);
+    let download_rcv_stats = summary(|(_, download)| download.rcv_mbps);
+    let download_send_stats = summary(|(_, download)| download.send_mbps);
+    let upload_jitter_stats = summary(|(upload, _)| upload.jitter_ms);
+    let upload_loss_stats = summary(|(upload, _)| upload.loss_percent);
+    let download_jitter_stats = summary(|(_, download)| download.jitter_ms);
+    let download_loss_stats = summary(|(_, download)| download.loss_percent);
+    let upload_retransmits_stats = summary(|(upload, _)| upload.retransmits as f64);
+    let download_retransmits_stats = summary(|(_, download)| download.retransmits as f64);
+    let upload_peer_cpu_stats = summary(|(upload, _)| upload.host_cpu_percent);
+    let upload_server_cpu_stats = summary(|(upload, _)| upload.remote_cpu_percent);
+    let download_peer_cpu_stats = summary(|(_, download)| download.host_cpu_percent);
+    let download_server_cpu_stats = summary(|(_, download)| download.remote_cpu_percent);
+
+    MtuTestResult {
+        server_mtu,
+        client_mtu,
+        upload_rcv_mbps: upload_rcv_stats.mean,
+        upload_send_mbps: upload_send_stats.mean,
+        download_rcv_mbps: download_rcv_stats.mean,
+        download_send_mbps: download_send_stats.mean,
+        upload_jitter_ms: upload_jitter_stats.mean,
+        upload_lost_packets: mean_count(|(upload, _)| upload.lost_packets),
+        upload_loss_percent: upload_loss_stats.mean,
+        download_jitter_ms: download_jitter_stats.mean,
+        download_lost_packets: mean_count(|(_, download)| download.lost_packets),
+        download_loss_percent: download_loss_stats.mean,
+        upload_retransmits: mean_count(|(upload, _)| upload.retransmits),
+        download_retransmits: mean_count(|(_, download)| download.retransmits),
+        upload_peer_cpu_percent: upload_peer_cpu_stats.mean,
+        upload_server_cpu_percent: upload_server_cpu_stats.mean,
+        download_peer_cpu_percent: download_peer_cpu_stats.mean,
+        download_server_cpu_percent: download_server_cpu_stats.mean,
+        repeats: runs.len() as u32,
+        upload_rcv_stats,
+        upload_send_stats,
+        download_rcv_stats,
+        download_send_stats,
+        upload_jitter_stats,
+        upload_loss_stats,
+        download_jitter_stats,
+        download_loss_stats,
+        upload_retransmits_stats,
+        download_retransmits_stats,
+        upload_peer_cpu_stats,
+        upload_server_cpu_stats,
+        download_peer_cpu_stats,
+        download_server_cpu_stats,
     }
 }
 
+// Функция для запроса установки MTU на стороне сервера
+pub(super) fn request_server_mtu<T: ControlTransport>(
+    stream: &mut T,
+    mtu: u32,
+) -> Result<u32, AppError> {
+    send_message(stream, Message::SetMtu(mtu))?;
+    get_remote_mtu(stream)
+}
+
 // Функция для запуска тестов скорости
-fn run_speed_tests(server_ip: &str, iperf_port: u16) -> Option<((f64, f64), (f64, f64))> {
+fn run_speed_tests<P: ThroughputTester>(
+    params: &PeerParameters,
+    config: &SweepConfig,
+    tester: &P,
+) -> Option<(ThroughputStats, ThroughputStats)> {
     // Выполнить тест скорости upload
     println!("Running upload test...");
-    let upload_result = run_iperf_test(server_ip, iperf_port, false);
+    let upload_result = tester.run_test(
+        config.engine,
+        target_ip(params),
+        config.iperf_port,
+        false,
+        &params.throughput,
+    );
 
     // Выполнить тест скорости download
     println!("Running download test...");
-    let download_result = run_iperf_test(server_ip, iperf_port, true);
+    let download_result = tester.run_test(
+        config.engine,
+        target_ip(params),
+        config.iperf_port,
+        true,
+        &params.throughput,
+    );
 
     match (upload_result, download_result) {
         (Some(upload), Some(download)) => Some((upload, download)),
         _ => None
     }
-}
\ No newline at end of file
+}
+
+// Функция для загрузки уже измеренных пар из CSV файла прерванного перебора
+fn load_completed_pairs(csv_file: &str) -> Result<PairScores, AppError> {
+    let data = read_csv_data(csv_file).map_err(|e| {
+        AppError::Output(format!(
+            "Failed to read results to resume from {}: {}",
+            csv_file, e
+        ))
+    })?;
+
+    Ok(data
+        .iter()
+        .map(|point| {
+            let pair = (point.server_mtu as u32, point.peer_mtu as u32);
+            (pair, point.upload_rcv_mbps.min(point.download_rcv_mbps))
+        })
+        .collect())
+}
+
+// Функция для получения списка проверяемых MTU по возрастанию
+pub(super) fn candidate_mtus(min_mtu: u32, max_mtu: u32, step: u32) -> Vec<u32> {
+    let mut mtus: Vec<u32> = (min_mtu..=max_mtu)
+        .rev()
+        .step_by(step.max(1) as usize)
+        .collect();
+    mtus.reverse();
+    mtus
+}
+
+// Оценка пары MTU: скорость в худшем из направлений
+pub(super) fn throughput_score(result: &MtuTestResult) -> f64 {
+    result.upload_rcv_mbps.min(result.download_rcv_mbps)
+}
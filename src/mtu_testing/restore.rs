@@ -0,0 +1,99 @@
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+use std::net::TcpStream;
+use std::process;
+use std::sync::{Mutex, Once};
+use std::thread;
+use crate::network::messages::{Message, try_send_message};
+use crate::network::mtu::{get_mtu, set_mtu};
+
+// Исходный MTU интерфейса, который нужно вернуть при выходе
+static ORIGINAL_MTU: Mutex<Option<(String, u32)>> = Mutex::new(None);
+// Управляющее соединение, по которому другой стороне отправляется Abort
+static CONTROL_STREAM: Mutex<Option<TcpStream>> = Mutex::new(None);
+static SIGNAL_HANDLER: Once = Once::new();
+
+// Возвращает интерфейсу исходный MTU при выходе из области видимости,
+// в том числе при панике, а также по SIGINT/SIGTERM
+pub(super) struct MtuRestoreGuard;
+
+impl MtuRestoreGuard {
+    pub(super) fn new(interface: &str) -> Option<Self> {
+        let mtu = match get_mtu(interface) {
+            Ok(mtu) => mtu,
+            Err(e) => {
+                eprintln!(
+                    "Warning: Could not read MTU of {}, it will not be restored: {}",
+                    interface, e
+                );
+                return None;
+            }
+        };
+        println!("Current MTU of interface {}: {}", interface, mtu);
+
+        *ORIGINAL_MTU.lock().unwrap_or_else(|e| e.into_inner()) =
+            Some((interface.to_string(), mtu));
+        SIGNAL_HANDLER.call_once(install_signal_handler);
+
+        Some(MtuRestoreGuard)
+    }
+}
+
+impl MtuRestoreGuard {
+    // Функция для отказа от восстановления: текущий MTU остается на интерфейсе
+    pub(super) fn keep_current(self) {
+        ORIGINAL_MTU.lock().unwrap_or_else(|e| e.into_inner()).take();
+    }
+}
+
+impl Drop for MtuRestoreGuard {
+    fn drop(&mut self) {
+        restore_original_mtu();
+    }
+}
+
+// Функция для восстановления исходного MTU (выполняется не более одного раза)
+fn restore_original_mtu() {
+    let original = ORIGINAL_MTU.lock().unwrap_or_else(|e| e.into_inner()).take();
+
+    if let Some((interface, mtu)) = original {
+        println!("Restoring original MTU {} on interface {}", mtu, interface);
+        if let Err(e) = set_mtu(&interface, mtu) {
+            eprintln!("Failed to restore MTU of {}: {}", interface, e);
+        }
+    }
+}
+
+// Функция для регистрации управляющего соединения: при получении сигнала
+// другая сторона получит Abort и сможет корректно завершиться
+pub(super) fn register_control_stream(stream: &TcpStream) {
+    match stream.try_clone() {
+        Ok(stream) => *CONTROL_STREAM.lock().unwrap_or_else(|e| e.into_inner()) = Some(stream),
+        Err(e) => eprintln!("Warning: Failed to register control connection: {}", e),
+    }
+}
+
+// Функция для установки обработчика SIGINT/SIGTERM
+fn install_signal_handler() {
+    let mut signals = match Signals::new([SIGINT, SIGTERM]) {
+        Ok(signals) => signals,
+        Err(e) => {
+            eprintln!("Warning: Failed to install signal handler: {}", e);
+            return;
+        }
+    };
+
+    thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            println!("Received signal {}, stopping", signal);
+
+            let stream = CONTROL_STREAM.lock().unwrap_or_else(|e| e.into_inner()).take();
+            if let Some(mut stream) = stream {
+                let _ = try_send_message(&mut stream, &Message::Abort);
+            }
+
+            restore_original_mtu();
+            process::exit(128 + signal);
+        }
+    });
+}
@@ -1,78 +1,244 @@
+use std::collections::HashSet;
 use std::net::{SocketAddr, TcpListener};
-use crate::data::models::TestParameters;
-use crate::network::messages::{Message, send_message, receive_message};
-use crate::network::iperf::check_iperf_installed;
-use crate::network::iperf::start_iperf_server;
-use crate::network::mtu::set_mtu;
-
-pub fn run_server(params: TestParameters) {
-    // Проверяем наличие iperf3
-    if !check_iperf_installed() {
-        eprintln!("Error: iperf3 is not installed or not in PATH");
-        return;
-    }
+use crate::data::models::{ServerEndpoints, SweepConfig, SweepStrategy, TestParameters};
+use crate::error::AppError;
+use crate::mtu_testing::apply::apply_requested_mtu;
+use crate::mtu_testing::handshake::{
+    announce_config, check_sweep_range, receive_completed_pairs, start_keepalive,
+};
+use crate::mtu_testing::peer::candidate_mtus;
+use crate::mtu_testing::restore::{MtuRestoreGuard, register_control_stream};
+use crate::network::messages::{
+    ControlTransport, Message, receive_control_message, report_error, send_message,
+    unexpected_message,
+};
+use crate::network::mtu::{MtuController, SystemMtu};
+use crate::network::throughput::{check_engine_available, start_throughput_server};
 
-    // Запустить iperf сервер
-    let mut iperf_process = start_iperf_server(params.iperf_port);
-    println!("Started iperf3 server on port {}", params.iperf_port);
+pub fn run_server(params: TestParameters) -> Result<(), AppError> {
+    // Пир перебирает тот же диапазон, поэтому проверяем его до запуска
+    check_sweep_range(params.min_mtu, params.max_mtu, params.step, params.refine_step)
+        .map_err(AppError::Config)?;
 
-    // Настроить сервер для контрольных сообщений
-    let addr = SocketAddr::from(([0, 0, 0, 0], params.control_port));
-    let listener = TcpListener::bind(addr).expect("Failed to bind to address");
-    println!("Server listening on port {}", params.control_port);
+    // Проверяем наличие выбранного движка
+    check_engine_available(params.engine)?;
+
+    // Запоминаем исходный MTU, чтобы вернуть его при выходе
+    let mtu_guard = MtuRestoreGuard::new(&params.interface);
+
+    // Запустить сервер замера скорости
+    let throughput_server = start_throughput_server(params.engine, params.iperf_port)?;
+    println!(
+        "Started {:?} throughput server on port {}",
+        params.engine, params.iperf_port
+    );
+
+    let result = serve_peer(&params);
 
-    let mut current_mtu = params.max_mtu;
-    let min_mtu = params.min_mtu;
-    let mut client_done = false;
+    // Остановить сервер замера скорости
+    throughput_server.stop();
+
+    // MTU, примененный по просьбе пира, остается на интерфейсе
+    if let (Ok(Some(_)), Some(guard)) = (&result, mtu_guard) {
+        guard.keep_current();
+    }
+    result.map(|_| ())
+}
+
+// Функция для приема пира по TCP и проведения перебора
+fn serve_peer(params: &TestParameters) -> Result<Option<u32>, AppError> {
+    // Настроить сервер для контрольных сообщений
+    let addr = SocketAddr::new(params.bind_ip, params.control_port);
+    let listener = TcpListener::bind(addr)
+        .map_err(|e| AppError::Connection(format!("Failed to bind to {}: {}", addr, e)))?;
+    println!("Server listening on {}", addr);
 
     // Принимаем первое соединение от клиента
     println!("Waiting for peer connection...");
-    let (mut stream, client_addr) = listener.accept().expect("Failed to accept connection");
+    let (mut stream, client_addr) = listener
+        .accept()
+        .map_err(|e| AppError::Connection(format!("Failed to accept connection: {}", e)))?;
     println!("Peer connected from: {}", client_addr);
+    register_control_stream(&stream);
+    let _heartbeat = start_keepalive(
+        &stream,
+        params.control_timeout_secs,
+        params.heartbeat_interval_secs,
+    )?;
+
+    let endpoints = ServerEndpoints {
+        control_ip: stream
+            .local_addr()
+            .map(|addr| addr.ip().to_string())
+            .unwrap_or_else(|_| params.bind_ip.to_string()),
+        tunnel_ip: params.tunnel_ip.clone(),
+    };
+    serve_sweep(params, &mut stream, &SystemMtu, &endpoints)
+}
+
+// Функция для проведения перебора с уже подключенным пиром.
+// MTU интерфейса меняется через mtu, сообщения идут через stream.
+// Возвращает MTU, оставленный на интерфейсе по просьбе пира (--apply-best).
+pub fn serve_sweep<T: ControlTransport, M: MtuController>(
+    params: &TestParameters,
+    stream: &mut T,
+    mtu: &M,
+    endpoints: &ServerEndpoints,
+) -> Result<Option<u32>, AppError> {
+    // Объявляем параметры перебора и текущий MTU сервера
+    let current_mtu = mtu.get_mtu(&params.interface).ok();
+    let config = SweepConfig {
+        min_mtu: params.min_mtu,
+        max_mtu: params.max_mtu,
+        step: params.step,
+        iperf_port: params.iperf_port,
+        engine: params.engine,
+        strategy: params.strategy,
+        refine_step: params.refine_step,
+    };
+    let apply_best = announce_config(stream, &config, current_mtu, endpoints)?;
+    println!("Peer accepted configuration: {:?}", config);
+
+    // Пары, уже измеренные в прерванном переборе
+    let completed = receive_completed_pairs(stream)?;
+    if !completed.is_empty() {
+        println!("Peer resumes sweep: {} pairs already tested", completed.len());
+    }
+
+    match params.strategy {
+        SweepStrategy::Grid => run_grid_sweep(params, stream, mtu, &completed)?,
+        SweepStrategy::Binary | SweepStrategy::Adaptive => {
+            serve_peer_requests(params, stream, mtu)?
+        }
+    }
+
+    println!("All MTU tests completed");
+
+    if !apply_best {
+        return Ok(None);
+    }
+    apply_requested_mtu(params, stream, mtu).map(Some)
+}
+
+// Функция для полного перебора: сервер сам перебирает свои значения MTU
+fn run_grid_sweep<T: ControlTransport, M: MtuController>(
+    params: &TestParameters,
+    stream: &mut T,
+    mtu: &M,
+    completed: &HashSet<(u32, u32)>,
+) -> Result<(), AppError> {
+    let client_mtus = candidate_mtus(params.min_mtu, params.max_mtu, params.step);
+
+    // Основной цикл тестирования MTU, от большего к меньшему
+    for &current_mtu in client_mtus.iter().rev() {
+        // Пропускаем значения, для которых пир уже измерил все пары
+        if client_mtus
+            .iter()
+            .all(|&client_mtu| completed.contains(&(current_mtu, client_mtu)))
+        {
+            println!("Skipping server MTU {}, already tested", current_mtu);
+            continue;
+        }
 
-    // Основной цикл тестирования MTU
-    while current_mtu >= min_mtu && !client_done {
         println!("Testing with server MTU: {}", current_mtu);
 
         // Установить MTU на интерфейсе
-        set_mtu(&params.interface, current_mtu);
+        if let Err(e) = mtu.set_mtu(&params.interface, current_mtu) {
+            report_error(
+                stream,
+                &format!("Failed to set server MTU {}: {}", current_mtu, e),
+            );
+            return Err(AppError::from(e));
+        }
 
         // Отправить сообщение о готовности сервера
-        send_message(&mut stream, Message::ServerReady);
+        send_message(stream, Message::ServerReady)?;
         println!("Sent ServerReady message to peer");
 
         // Отправляем текущее значение MTU клиенту
-        send_message(&mut stream, Message::MtuValue(current_mtu));
+        send_message(stream, Message::MtuValue(current_mtu))?;
 
-        // Ждать завершения тестов со стороны пира
-        match receive_message::<Message>(&mut stream) {
-            Ok(Message::PeerDone) => {
-                println!("Peer completed tests for server MTU {}", current_mtu);
+        // Ждать завершения тестов со стороны пира, запоминая измеряемую пару
+        let mut peer_mtu = None;
+        loop {
+            let message = receive_control_message(stream, "Peer")
+                .map_err(|e| e.context(pair_in_progress(current_mtu, peer_mtu)))?;
+            match message {
+                Message::PeerMtu(mtu) => peer_mtu = Some(mtu),
+                Message::PeerDone => {
+                    println!("Peer completed tests for server MTU {}", current_mtu);
+                    break;
+                }
+                other => {
+                    return Err(unexpected_message(stream, "peer", "PeerMtu or PeerDone", &other));
+                }
+            }
+        }
+    }
 
-                // Уменьшаем MTU для следующего теста
-                current_mtu -= params.step;
+    // Достигли минимального MTU, отправляем сигнал о завершении
+    println!("All tests completed, sending Finish signal to peer");
+    send_message(stream, Message::Finish)?;
+    Ok(())
+}
 
-                // Если достигли минимального MTU, отправляем сигнал о завершении
-                if current_mtu < min_mtu {
-                    println!("All tests completed, sending Finish signal to peer");
-                    send_message(&mut stream, Message::Finish);
-                    client_done = true;
+// Функция для поисковых стратегий: значения MTU сервера выбирает пир
+fn serve_peer_requests<T: ControlTransport, M: MtuController>(
+    params: &TestParameters,
+    stream: &mut T,
+    mtu_controller: &M,
+) -> Result<(), AppError> {
+    let mut current_mtu = None;
+    let mut peer_mtu = None;
+
+    loop {
+        let message = receive_control_message(stream, "Peer").map_err(|e| match current_mtu {
+            Some(mtu) => e.context(pair_in_progress(mtu, peer_mtu)),
+            None => e,
+        })?;
+        match message {
+            Message::PeerMtu(mtu) => peer_mtu = Some(mtu),
+            Message::SetMtu(mtu) => {
+                println!("Peer requested server MTU: {}", mtu);
+
+                // Пир может просить только значения из согласованного диапазона
+                if !(params.min_mtu..=params.max_mtu).contains(&mtu) {
+                    let reason = format!(
+                        "Requested server MTU {} is outside the negotiated range {}..{}",
+                        mtu, params.min_mtu, params.max_mtu
+                    );
+                    report_error(stream, &reason);
+                    return Err(AppError::Protocol(reason));
                 }
-            },
-            Ok(_) => {
-                println!("Unexpected message from peer");
-                break;
-            },
-            Err(e) => {
-                println!("Error receiving message from peer: {}", e);
-                break;
+
+                if let Err(e) = mtu_controller.set_mtu(&params.interface, mtu) {
+                    report_error(stream, &format!("Failed to set server MTU {}: {}", mtu, e));
+                    return Err(AppError::from(e));
+                }
+
+                // Подтверждаем установленное значение
+                current_mtu = Some(mtu);
+                peer_mtu = None;
+                send_message(stream, Message::MtuValue(mtu))?;
+            }
+            Message::Finish => {
+                println!("Peer signals testing is complete");
+                return Ok(());
+            }
+            other => {
+                return Err(unexpected_message(stream, "peer", "SetMtu, PeerMtu or Finish", &other));
             }
         }
     }
+}
 
-    println!("All MTU tests completed");
-    // Завершить процесс iperf
-    iperf_process
-        .kill()
-        .expect("Failed to kill iperf3 server process");
-}
\ No newline at end of file
+// Функция для описания пары, которую измерял пир, когда связь с ним оборвалась
+fn pair_in_progress(server_mtu: u32, peer_mtu: Option<u32>) -> String {
+    match peer_mtu {
+        Some(peer_mtu) => format!(
+            "while peer was testing server MTU {} with peer MTU {}",
+            server_mtu, peer_mtu
+        ),
+        None => format!("while peer was testing with server MTU {}", server_mtu),
+    }
+}
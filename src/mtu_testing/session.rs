@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::fs::File;
+use csv::Writer;
+use crate::data::models::{PeerParameters, SweepConfig};
+use crate::error::AppError;
+use crate::mtu_testing::peer::{measure_pair, request_server_mtu, throughput_score};
+use crate::network::messages::{ControlTransport, Message, report_error, send_message};
+use crate::network::mtu::MtuController;
+use crate::network::throughput::ThroughputTester;
+
+// Оценки измеренных пар (server_mtu, client_mtu)
+pub(super) type PairScores = HashMap<(u32, u32), f64>;
+
+// Сеанс тестирования, в котором значения MTU сервера выбирает пир
+pub(super) struct ProbeSession<'a, T, M, P> {
+    pub(super) params: &'a PeerParameters,
+    pub(super) config: &'a SweepConfig,
+    stream: &'a mut T,
+    mtu: &'a M,
+    tester: &'a P,
+    writer: &'a mut Writer<File>,
+    server_mtu: Option<u32>,
+    // Оценки уже измеренных пар, включая пары из прерванного перебора
+    pub(super) scores: PairScores,
+}
+
+impl<'a, T: ControlTransport, M: MtuController, P: ThroughputTester> ProbeSession<'a, T, M, P> {
+    pub(super) fn new(
+        params: &'a PeerParameters,
+        config: &'a SweepConfig,
+        stream: &'a mut T,
+        mtu: &'a M,
+        tester: &'a P,
+        writer: &'a mut Writer<File>,
+        completed: PairScores,
+    ) -> Self {
+        ProbeSession {
+            params,
+            config,
+            stream,
+            mtu,
+            tester,
+            writer,
+            server_mtu: None,
+            scores: completed,
+        }
+    }
+
+    // Функция для измерения одной пары; возвращает min(upload, download) в Mbps.
+    // Повторно пары не измеряются, ошибка означает, что перебор нужно прервать.
+    pub(super) fn probe(&mut self, server_mtu: u32, client_mtu: u32) -> Result<f64, AppError> {
+        if let Some(&score) = self.scores.get(&(server_mtu, client_mtu)) {
+            return Ok(score);
+        }
+
+        if self.server_mtu != Some(server_mtu) {
+            let mtu = request_server_mtu(self.stream, server_mtu).map_err(|e| {
+                e.context(format!(
+                    "while switching to server MTU {} for client MTU {}",
+                    server_mtu, client_mtu
+                ))
+            })?;
+            println!("Server MTU: {}", mtu);
+            self.server_mtu = Some(mtu);
+        }
+
+        let result = measure_pair(
+            self.params,
+            self.config,
+            self.stream,
+            self.mtu,
+            self.tester,
+            server_mtu,
+            client_mtu,
+            self.writer,
+        )
+        .map_err(|e| {
+            report_error(self.stream, &e.to_string());
+            e.context(format!("while testing with server MTU {}", server_mtu))
+        })?;
+        let score = result.as_ref().map(throughput_score).unwrap_or(0.0);
+        self.scores.insert((server_mtu, client_mtu), score);
+
+        Ok(score)
+    }
+
+    // Функция для поиска лучшей из измеренных пар
+    pub(super) fn best(&self) -> Option<((u32, u32), f64)> {
+        self.scores
+            .iter()
+            .map(|(&pair, &score)| (pair, score))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    // Функция для завершения сеанса
+    pub(super) fn finish(self) -> Result<(), AppError> {
+        println!("All tests completed, sending Finish signal to server");
+        send_message(self.stream, Message::Finish)
+    }
+}
@@ -0,0 +1,92 @@
+use std::thread;
+use std::time::Duration;
+use crate::data::models::{
+    DEFAULT_IPERF_PORT, DEFAULT_MAX_MTU, DEFAULT_MIN_MTU, DEFAULT_REFINE_STEP,
+    DEFAULT_SIM_SERVER_IP, DEFAULT_STEP, PeerParameters, ServerEndpoints, SimulationModel,
+    SweepStrategy, TestParameters, ThroughputEngine,
+};
+use crate::error::AppError;
+use crate::mtu_testing::peer::run_peer_sweep;
+use crate::mtu_testing::server::serve_sweep;
+use crate::network::memory::{MemoryLinks, MemoryThroughput, memory_transport_pair};
+use crate::network::simulation::SimulatedNetwork;
+
+// Имя интерфейса сервера в модели
+const SIMULATED_SERVER_INTERFACE: &str = "sim-server";
+
+// Функция для перебора на модели сети: сервер работает в том же процессе,
+// MTU интерфейсов хранятся в памяти, скорость рассчитывает модель
+pub(super) fn run_simulation(
+    params: &PeerParameters,
+    model: &SimulationModel,
+) -> Result<(), AppError> {
+    println!(
+        "Simulating network: underlay MTU {}, overhead {} bytes, {} Mbps, fragmentation penalty {}, noise {}",
+        model.underlay_mtu,
+        model.overhead,
+        model.bandwidth_mbps,
+        model.fragmentation_penalty,
+        model.noise
+    );
+
+    // Интерфейсы туннеля начинают с MTU, который выбрал бы WireGuard
+    let tunnel_mtu = model.underlay_mtu.saturating_sub(model.overhead);
+    let links = MemoryLinks::new();
+    links.add(SIMULATED_SERVER_INTERFACE, tunnel_mtu);
+    links.add(&params.interface, tunnel_mtu);
+
+    // Сервер использует параметры, явно заданные пиром, остальные по умолчанию
+    let requested = &params.requested;
+    let server_params = TestParameters {
+        interface: SIMULATED_SERVER_INTERFACE.to_string(),
+        min_mtu: requested.min_mtu.unwrap_or(DEFAULT_MIN_MTU),
+        max_mtu: requested.max_mtu.unwrap_or(DEFAULT_MAX_MTU),
+        step: requested.step.unwrap_or(DEFAULT_STEP),
+        control_port: params.control_port,
+        bind_ip: [127, 0, 0, 1].into(),
+        tunnel_ip: None,
+        iperf_port: requested.iperf_port.unwrap_or(DEFAULT_IPERF_PORT),
+        engine: requested.engine.unwrap_or(ThroughputEngine::Native),
+        strategy: requested.strategy.unwrap_or(SweepStrategy::Grid),
+        refine_step: requested.refine_step.unwrap_or(DEFAULT_REFINE_STEP),
+        control_timeout_secs: params.control_timeout_secs,
+        heartbeat_interval_secs: params.heartbeat_interval_secs,
+    };
+    // Модель объявляет адрес, заданный пиром, чтобы он прошел проверку
+    let address = params
+        .server_ip
+        .clone()
+        .unwrap_or_else(|| DEFAULT_SIM_SERVER_IP.to_string());
+    let endpoints = ServerEndpoints {
+        control_ip: address.clone(),
+        tunnel_ip: Some(address),
+    };
+
+    let timeout = Duration::from_secs(params.control_timeout_secs);
+    let (mut server_stream, mut peer_stream) = memory_transport_pair(Some(timeout));
+    let server_links = links.clone();
+    let server = thread::spawn(move || {
+        serve_sweep(&server_params, &mut server_stream, &server_links, &endpoints)
+    });
+
+    let network = SimulatedNetwork::new(model.clone());
+    let options = params.throughput.clone();
+    let tester = MemoryThroughput::new(
+        links.clone(),
+        SIMULATED_SERVER_INTERFACE,
+        &params.interface,
+        move |server_mtu, peer_mtu, is_download| {
+            Some(network.measure(server_mtu, peer_mtu, is_download, &options))
+        },
+    );
+
+    let peer_result = run_peer_sweep(params, &mut peer_stream, &links, &tester);
+
+    // Ошибку пира показываем в первую очередь, сервер лишь отражает ее
+    drop(peer_stream);
+    let server_result = server
+        .join()
+        .unwrap_or_else(|_| Err(AppError::Protocol("Simulated server panicked".to_string())));
+    peer_result.and(server_result.map_err(|e| e.context("in simulated server")))?;
+    Ok(())
+}
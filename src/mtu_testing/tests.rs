@@ -0,0 +1,560 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use crate::data::models::{
+    DEFAULT_CONTROL_TIMEOUT_SECS, DEFAULT_HEARTBEAT_INTERVAL_SECS, DEFAULT_IPERF_PORT,
+    PeerParameters, ServerEndpoints, SweepRequest, SweepStrategy, TestParameters, TestProtocol,
+    ThroughputEngine, ThroughputOptions, ThroughputStats,
+};
+use crate::error::{
+    AppError, EXIT_ABORTED, EXIT_CONFIG, EXIT_CONNECTION, EXIT_MTU, EXIT_PROTOCOL, EXIT_REMOTE,
+};
+use crate::heatmap::data_reader::read_csv_data;
+use crate::mtu_testing::{run_peer_sweep, serve_sweep};
+use crate::network::memory::{
+    MemoryLinks, MemoryThroughput, MemoryTransport, memory_transport_pair,
+};
+use crate::network::messages::{ControlTransport, Message, PROTOCOL_VERSION};
+use crate::utils::metadata::{load_metadata, metadata_path};
+
+const SERVER_INTERFACE: &str = "test-server";
+const PEER_INTERFACE: &str = "test-peer";
+const CONTROL_IP: &str = "control";
+const TUNNEL_IP: &str = "tunnel";
+const ORIGINAL_MTU: u32 = 1500;
+const MIN_MTU: u32 = 1360;
+const MAX_MTU: u32 = 1460;
+const STEP: u32 = 20;
+const REFINE_STEP: u32 = 10;
+// Above this MTU on either side the model's throughput collapses
+const COLLAPSE_MTU: u32 = 1430;
+
+// Results of one sweep run by serve_sweep and run_peer_sweep against each other
+struct SweepRun {
+    server: Result<Option<u32>, AppError>,
+    peer: Result<Option<(u32, u32)>, AppError>,
+    // Pairs measured by the peer, in order
+    probed: Vec<(u32, u32)>,
+    links: MemoryLinks,
+    csv_file: CsvFile,
+}
+
+// CSV file of a test run, removed together with its metadata
+struct CsvFile(PathBuf);
+
+impl CsvFile {
+    fn new(name: &str) -> Self {
+        let file_name = format!("wg_mtu_finder_{}_{}.csv", name, process::id());
+        CsvFile(std::env::temp_dir().join(file_name))
+    }
+
+    fn path(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+}
+
+impl Drop for CsvFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+        let _ = fs::remove_file(metadata_path(self.path()));
+    }
+}
+
+// Server transport that sends Abort instead of every message after the first
+// sends_left, as the signal handler does on Ctrl-C
+struct AbortingTransport {
+    inner: MemoryTransport,
+    sends_left: usize,
+}
+
+impl ControlTransport for AbortingTransport {
+    fn send_frame(&mut self, message: &Message) -> io::Result<()> {
+        if self.sends_left == 0 {
+            return self.inner.send_frame(&Message::Abort);
+        }
+        self.sends_left -= 1;
+        self.inner.send_frame(message)
+    }
+
+    fn receive_frame(&mut self) -> io::Result<Message> {
+        self.inner.receive_frame()
+    }
+}
+
+// Throughput grows with the MTU of both sides and collapses above COLLAPSE_MTU,
+// so every strategy has a single best pair
+fn model(server_mtu: u32, peer_mtu: u32) -> ThroughputStats {
+    let mbps = if server_mtu <= COLLAPSE_MTU && peer_mtu <= COLLAPSE_MTU {
+        f64::from(server_mtu + peer_mtu) / 4.0
+    } else {
+        50.0
+    };
+    ThroughputStats {
+        rcv_mbps: mbps,
+        send_mbps: mbps,
+        ..Default::default()
+    }
+}
+
+fn server_params(strategy: SweepStrategy) -> TestParameters {
+    TestParameters {
+        interface: SERVER_INTERFACE.to_string(),
+        min_mtu: MIN_MTU,
+        max_mtu: MAX_MTU,
+        step: STEP,
+        control_port: 0,
+        bind_ip: [127, 0, 0, 1].into(),
+        tunnel_ip: None,
+        iperf_port: DEFAULT_IPERF_PORT,
+        engine: ThroughputEngine::Native,
+        strategy,
+        control_timeout_secs: DEFAULT_CONTROL_TIMEOUT_SECS,
+        heartbeat_interval_secs: DEFAULT_HEARTBEAT_INTERVAL_SECS,
+        refine_step: REFINE_STEP,
+    }
+}
+
+// Addresses the server announces to the peer
+fn endpoints() -> ServerEndpoints {
+    ServerEndpoints {
+        control_ip: CONTROL_IP.to_string(),
+        tunnel_ip: Some(TUNNEL_IP.to_string()),
+    }
+}
+
+fn peer_params(csv_file: &CsvFile) -> PeerParameters {
+    PeerParameters {
+        interface: PEER_INTERFACE.to_string(),
+        server_ip: None,
+        control_ip: Some(CONTROL_IP.to_string()),
+        control_port: 0,
+        control_timeout_secs: DEFAULT_CONTROL_TIMEOUT_SECS,
+        heartbeat_interval_secs: DEFAULT_HEARTBEAT_INTERVAL_SECS,
+        repeats: 1,
+        csv_file: csv_file.path().to_string(),
+        resume: false,
+        apply_best: false,
+        requested: SweepRequest::default(),
+        throughput: ThroughputOptions {
+            protocol: TestProtocol::Tcp,
+            udp_bitrate_mbps: 0.0,
+            udp_payload_size: 0,
+            duration_secs: 1,
+            omit_secs: 0,
+            parallel_streams: 1,
+            window_bytes: None,
+            congestion: None,
+        },
+        simulation: None,
+    }
+}
+
+// Function to run both ends of a sweep over the in-memory transport.
+// setup adjusts the peer parameters, wrap_server decides what the server's
+// side of the connection looks like.
+fn run_sweep<T: ControlTransport + Send + 'static>(
+    name: &str,
+    server: TestParameters,
+    setup: impl FnOnce(&mut PeerParameters),
+    links: MemoryLinks,
+    wrap_server: impl FnOnce(MemoryTransport) -> T,
+) -> SweepRun {
+    let csv_file = CsvFile::new(name);
+    let mut peer = peer_params(&csv_file);
+    setup(&mut peer);
+
+    let (server_stream, mut peer_stream) = memory_transport_pair(Some(Duration::from_secs(5)));
+    let mut server_stream = wrap_server(server_stream);
+    let server_links = links.clone();
+    let server_thread =
+        thread::spawn(move || serve_sweep(&server, &mut server_stream, &server_links, &endpoints()));
+
+    let probed = Arc::new(Mutex::new(Vec::new()));
+    let recorded = Arc::clone(&probed);
+    let tester = MemoryThroughput::new(
+        links.clone(),
+        SERVER_INTERFACE,
+        PEER_INTERFACE,
+        move |server_mtu, peer_mtu, is_download| {
+            if !is_download {
+                recorded.lock().unwrap().push((server_mtu, peer_mtu));
+            }
+            Some(model(server_mtu, peer_mtu))
+        },
+    );
+    let peer_result = run_peer_sweep(&peer, &mut peer_stream, &links, &tester);
+
+    // The server may be waiting for the peer, which is gone once its end is dropped
+    drop(peer_stream);
+    let server_result = server_thread.join().unwrap();
+    let probed = probed.lock().unwrap().clone();
+
+    SweepRun {
+        server: server_result,
+        peer: peer_result,
+        probed,
+        links,
+        csv_file,
+    }
+}
+
+fn links_with_both_interfaces() -> MemoryLinks {
+    let links = MemoryLinks::new();
+    links.add(SERVER_INTERFACE, ORIGINAL_MTU);
+    links.add(PEER_INTERFACE, ORIGINAL_MTU);
+    links
+}
+
+fn unique(pairs: &[(u32, u32)]) -> HashSet<(u32, u32)> {
+    pairs.iter().copied().collect()
+}
+
+fn coarse_mtus() -> Vec<u32> {
+    (MIN_MTU..=MAX_MTU).step_by(STEP as usize).collect()
+}
+
+#[test]
+fn grid_probes_every_pair_once_from_the_top() {
+    let run = run_sweep(
+        "grid",
+        server_params(SweepStrategy::Grid),
+        |_| {},
+        links_with_both_interfaces(),
+        |stream| stream,
+    );
+    assert_eq!(run.server.unwrap(), None);
+    assert_eq!(run.peer.unwrap(), None);
+
+    let mut expected = Vec::new();
+    for server_mtu in coarse_mtus().into_iter().rev() {
+        for peer_mtu in coarse_mtus().into_iter().rev() {
+            expected.push((server_mtu, peer_mtu));
+        }
+    }
+    assert_eq!(run.probed, expected);
+
+    let saved: Vec<(u32, u32)> = read_csv_data(run.csv_file.path())
+        .unwrap()
+        .iter()
+        .map(|point| (point.server_mtu.into(), point.peer_mtu.into()))
+        .collect();
+    assert_eq!(saved, expected);
+
+    // Without --server-ip the peer tests against the announced tunnel address
+    let metadata = load_metadata(run.csv_file.path()).unwrap();
+    assert_eq!(metadata.server_ip, TUNNEL_IP);
+
+    // Both interfaces are left on the last tested pair, the original MTU is
+    // restored by run_server and run_peer
+    assert_eq!(run.links.mtu(SERVER_INTERFACE), Some(MIN_MTU));
+    assert_eq!(run.links.mtu(PEER_INTERFACE), Some(MIN_MTU));
+}
+
+#[test]
+fn binary_search_finds_the_largest_stable_pair() {
+    let run = run_sweep(
+        "binary",
+        server_params(SweepStrategy::Binary),
+        |_| {},
+        links_with_both_interfaces(),
+        |stream| stream,
+    );
+    run.server.unwrap();
+    run.peer.unwrap();
+
+    // Baseline first, then bisect the server MTU at the lowest peer MTU,
+    // then the peer MTU at the largest stable server MTU, 1420
+    let expected = vec![
+        (1360, 1360),
+        (1460, 1360),
+        (1400, 1360),
+        (1420, 1360),
+        (1440, 1360),
+        (1420, 1460),
+        (1420, 1400),
+        (1420, 1420),
+        (1420, 1440),
+    ];
+    assert_eq!(run.probed, expected);
+}
+
+#[test]
+fn adaptive_refines_around_the_best_coarse_pair() {
+    let run = run_sweep(
+        "adaptive",
+        server_params(SweepStrategy::Adaptive),
+        |_| {},
+        links_with_both_interfaces(),
+        |stream| stream,
+    );
+    run.server.unwrap();
+    run.peer.unwrap();
+
+    // The whole coarse grid, then the fine grid one coarse step around 1420/1420
+    let coarse = coarse_mtus().len();
+    let fine: Vec<u32> = (1400..=1440).step_by(REFINE_STEP as usize).collect();
+    let probed = unique(&run.probed);
+    assert_eq!(probed.len(), run.probed.len());
+    assert!(run.probed[..coarse * coarse].iter().all(|pair| {
+        coarse_mtus().contains(&pair.0) && coarse_mtus().contains(&pair.1)
+    }));
+    for &server_mtu in &fine {
+        for &peer_mtu in &fine {
+            assert!(probed.contains(&(server_mtu, peer_mtu)));
+        }
+    }
+    // Coarse pairs of the fine grid are not measured again
+    let fine_only = fine.iter().filter(|mtu| !coarse_mtus().contains(mtu)).count();
+    let expected = coarse * coarse + fine.len() * fine.len() - (fine.len() - fine_only).pow(2);
+    assert_eq!(run.probed.len(), expected);
+}
+
+#[test]
+fn apply_best_leaves_the_recommended_pair_on_both_ends() {
+    let run = run_sweep(
+        "apply_best",
+        server_params(SweepStrategy::Grid),
+        |peer| peer.apply_best = true,
+        links_with_both_interfaces(),
+        |stream| stream,
+    );
+
+    // The plateau covers 1360..1420 on both sides, 1400/1400 is deepest inside it
+    assert_eq!(run.peer.unwrap(), Some((1400, 1400)));
+    assert_eq!(run.server.unwrap(), Some(1400));
+    assert_eq!(run.links.mtu(SERVER_INTERFACE), Some(1400));
+    assert_eq!(run.links.mtu(PEER_INTERFACE), Some(1400));
+}
+
+#[test]
+fn peer_rejects_a_server_ip_that_disagrees_with_the_tunnel() {
+    let run = run_sweep(
+        "server_ip_mismatch",
+        server_params(SweepStrategy::Grid),
+        |peer| peer.server_ip = Some("elsewhere".to_string()),
+        links_with_both_interfaces(),
+        |stream| stream,
+    );
+
+    let peer_error = run.peer.unwrap_err();
+    assert_eq!(peer_error.exit_code(), EXIT_CONFIG);
+    assert!(peer_error.to_string().contains(TUNNEL_IP));
+    assert_eq!(run.server.unwrap_err().exit_code(), EXIT_REMOTE);
+    assert!(run.probed.is_empty());
+}
+
+#[test]
+fn server_error_stops_the_peer() {
+    // The server interface is missing, so the server cannot set its MTU
+    let links = MemoryLinks::new();
+    links.add(PEER_INTERFACE, ORIGINAL_MTU);
+    let run = run_sweep("server_error", server_params(SweepStrategy::Grid), |_| {}, links, |stream| {
+        stream
+    });
+
+    assert_eq!(run.server.unwrap_err().exit_code(), EXIT_MTU);
+    let peer_error = run.peer.unwrap_err();
+    assert_eq!(peer_error.exit_code(), EXIT_REMOTE);
+    assert!(peer_error.to_string().contains("Failed to set server MTU"));
+    assert!(run.probed.is_empty());
+    assert_eq!(run.links.mtu(PEER_INTERFACE), Some(ORIGINAL_MTU));
+}
+
+#[test]
+fn peer_error_stops_the_server() {
+    // The peer interface is missing, so the peer cannot set its MTU
+    let links = MemoryLinks::new();
+    links.add(SERVER_INTERFACE, ORIGINAL_MTU);
+    let run = run_sweep("peer_error", server_params(SweepStrategy::Binary), |_| {}, links, |stream| {
+        stream
+    });
+
+    assert_eq!(run.peer.unwrap_err().exit_code(), EXIT_MTU);
+    let server_error = run.server.unwrap_err();
+    assert_eq!(server_error.exit_code(), EXIT_REMOTE);
+    assert!(server_error.to_string().contains("Peer reported an error"));
+    assert!(run.probed.is_empty());
+}
+
+#[test]
+fn abort_stops_the_sweep_and_keeps_measured_pairs() {
+    // Config, ServerReady and MtuValue go through, the second ServerReady becomes Abort
+    let run = run_sweep(
+        "abort",
+        server_params(SweepStrategy::Grid),
+        |_| {},
+        links_with_both_interfaces(),
+        |inner| AbortingTransport {
+            inner,
+            sends_left: 3,
+        },
+    );
+
+    let peer_error = run.peer.unwrap_err();
+    assert_eq!(peer_error.exit_code(), EXIT_ABORTED);
+    assert!(peer_error.to_string().contains("Server aborted the sweep"));
+    assert_eq!(run.server.unwrap_err().exit_code(), EXIT_CONNECTION);
+
+    let first_row: Vec<(u32, u32)> = coarse_mtus().into_iter().rev().map(|mtu| (MAX_MTU, mtu)).collect();
+    assert_eq!(run.probed, first_row);
+    assert_eq!(read_csv_data(run.csv_file.path()).unwrap().len(), first_row.len());
+}
+
+#[test]
+fn server_timeout_names_the_pair_in_progress() {
+    let csv_file = CsvFile::new("timeout");
+    let peer = peer_params(&csv_file);
+    let links = links_with_both_interfaces();
+    let server_links = links.clone();
+    let (mut server_stream, mut peer_stream) =
+        memory_transport_pair(Some(Duration::from_millis(200)));
+    let server = thread::spawn(move || {
+        let params = server_params(SweepStrategy::Grid);
+        serve_sweep(&params, &mut server_stream, &server_links, &endpoints())
+    });
+
+    // The peer hangs on its second pair for longer than the server waits
+    let hung = (MAX_MTU, MAX_MTU - STEP);
+    let tester = MemoryThroughput::new(
+        links.clone(),
+        SERVER_INTERFACE,
+        PEER_INTERFACE,
+        move |server_mtu, peer_mtu, is_download| {
+            if !is_download && (server_mtu, peer_mtu) == hung {
+                thread::sleep(Duration::from_millis(600));
+            }
+            Some(model(server_mtu, peer_mtu))
+        },
+    );
+    let peer_result = run_peer_sweep(&peer, &mut peer_stream, &links, &tester);
+    drop(peer_stream);
+
+    let server_error = server.join().unwrap().unwrap_err();
+    assert_eq!(server_error.exit_code(), EXIT_CONNECTION);
+    let message = server_error.to_string();
+    assert!(message.contains("server MTU 1460 with peer MTU 1440"), "{}", message);
+    assert!(peer_result.is_err());
+}
+
+// Function to run a grid sweep that the server aborts after the first row
+fn interrupted_sweep(name: &str) -> SweepRun {
+    run_sweep(
+        name,
+        server_params(SweepStrategy::Grid),
+        |_| {},
+        links_with_both_interfaces(),
+        |inner| AbortingTransport {
+            inner,
+            sends_left: 3,
+        },
+    )
+}
+
+#[test]
+fn resume_with_the_same_settings_tests_only_the_missing_pairs() {
+    let interrupted = interrupted_sweep("resume_same");
+    let done = unique(&interrupted.probed);
+
+    let run = run_sweep(
+        "resume_same",
+        server_params(SweepStrategy::Grid),
+        |peer| peer.resume = true,
+        links_with_both_interfaces(),
+        |stream| stream,
+    );
+    run.server.unwrap();
+    run.peer.unwrap();
+
+    let all = coarse_mtus().len() * coarse_mtus().len();
+    assert_eq!(run.probed.len(), all - done.len());
+    assert!(run.probed.iter().all(|pair| !done.contains(pair)));
+    assert_eq!(read_csv_data(run.csv_file.path()).unwrap().len(), all);
+}
+
+#[test]
+fn resume_rejects_different_settings() {
+    let mut changed_step = server_params(SweepStrategy::Grid);
+    changed_step.step = STEP / 2;
+    let mut changed_engine = server_params(SweepStrategy::Grid);
+    changed_engine.engine = ThroughputEngine::Iperf3;
+    let resume: fn(&mut PeerParameters) = |peer| peer.resume = true;
+    let resume_longer: fn(&mut PeerParameters) = |peer| {
+        peer.resume = true;
+        peer.throughput.duration_secs += 1;
+    };
+    let cases = [
+        (changed_step, resume, "--step was 20 but is now 10"),
+        (changed_engine, resume, "--engine was Native but is now Iperf3"),
+        (server_params(SweepStrategy::Grid), resume_longer, "--duration was 1 but is now 2"),
+    ];
+
+    for (server, setup, changed) in cases {
+        let interrupted = interrupted_sweep("resume_changed");
+        let rows = read_csv_data(interrupted.csv_file.path()).unwrap().len();
+
+        let run = run_sweep("resume_changed", server, setup, links_with_both_interfaces(), |stream| {
+            stream
+        });
+
+        let peer_error = run.peer.unwrap_err();
+        assert_eq!(peer_error.exit_code(), EXIT_CONFIG);
+        assert!(peer_error.to_string().contains(changed), "{}", peer_error);
+        assert_eq!(run.server.unwrap_err().exit_code(), EXIT_REMOTE);
+        assert!(run.probed.is_empty());
+
+        // The interrupted run is left as it was
+        assert_eq!(read_csv_data(run.csv_file.path()).unwrap().len(), rows);
+        let metadata = load_metadata(run.csv_file.path()).unwrap();
+        assert_eq!(metadata.step, STEP);
+        assert_eq!(metadata.engine, ThroughputEngine::Native);
+        assert_eq!(metadata.throughput.duration_secs, 1);
+    }
+}
+
+#[test]
+fn peer_rejects_an_invalid_announced_range() {
+    let mut server = server_params(SweepStrategy::Grid);
+    server.min_mtu = MAX_MTU;
+    server.max_mtu = MIN_MTU;
+    let run = run_sweep("invalid_range", server, |_| {}, links_with_both_interfaces(), |stream| {
+        stream
+    });
+
+    let peer_error = run.peer.unwrap_err();
+    assert_eq!(peer_error.exit_code(), EXIT_CONFIG);
+    assert!(peer_error.to_string().contains("invalid sweep parameters"));
+    assert_eq!(run.server.unwrap_err().exit_code(), EXIT_REMOTE);
+    assert!(run.probed.is_empty());
+}
+
+#[test]
+fn server_rejects_a_requested_mtu_outside_the_range() {
+    let links = links_with_both_interfaces();
+    let server_links = links.clone();
+    let (mut server_stream, mut peer_stream) = memory_transport_pair(Some(Duration::from_secs(5)));
+    let server = thread::spawn(move || {
+        let params = server_params(SweepStrategy::Binary);
+        serve_sweep(&params, &mut server_stream, &server_links, &endpoints())
+    });
+
+    // Peer side of the handshake, then a request the server must refuse
+    let version = PROTOCOL_VERSION;
+    peer_stream.send_frame(&Message::Hello { version }).unwrap();
+    assert!(matches!(peer_stream.receive_frame().unwrap(), Message::Config { .. }));
+    let accepted = Message::ConfigAccepted { apply_best: false };
+    peer_stream.send_frame(&accepted).unwrap();
+    peer_stream.send_frame(&Message::CompletedPairs(Vec::new())).unwrap();
+    peer_stream.send_frame(&Message::SetMtu(MAX_MTU + STEP)).unwrap();
+
+    match peer_stream.receive_frame().unwrap() {
+        Message::Error { reason } => assert!(reason.contains("outside the negotiated range")),
+        other => panic!("expected Error, got {:?}", other),
+    }
+    assert_eq!(server.join().unwrap().unwrap_err().exit_code(), EXIT_PROTOCOL);
+    assert_eq!(links.mtu(SERVER_INTERFACE), Some(ORIGINAL_MTU));
+}
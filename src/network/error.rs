@@ -0,0 +1,24 @@
+use std::io;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum MtuError {
+    #[error("Interface {0} not found")]
+    InterfaceNotFound(String),
+
+    #[error("Permission denied, changing MTU of {0} requires CAP_NET_ADMIN")]
+    PermissionDenied(String),
+
+    #[error("Kernel rejected MTU {mtu} on interface {interface}")]
+    InvalidMtu { interface: String, mtu: u32 },
+
+    #[error("Interface {interface} reports MTU {actual}, expected {expected}")]
+    NotApplied {
+        interface: String,
+        expected: u32,
+        actual: u32,
+    },
+
+    #[error("Netlink error: {0}")]
+    Netlink(#[from] io::Error),
+}
@@ -1,5 +1,7 @@
+use crate::data::models::{TestProtocol, ThroughputOptions, ThroughputStats};
 use serde_json::Value;
-use std::process::{Command, Stdio};
+use std::io;
+use std::process::{Child, Command, Stdio};
 
 // Функция для проверки установки iperf
 pub fn check_iperf_installed() -> bool {
@@ -11,17 +13,21 @@ pub fn check_iperf_installed() -> bool {
 }
 
 // Функция для запуска iperf сервера
-pub fn start_iperf_server(port: u16) -> std::process::Child {
+pub fn start_iperf_server(port: u16) -> io::Result<Child> {
     Command::new("iperf3")
-        .args(&["-s", "-p", &port.to_string()])
+        .args(["-s", "-p", &port.to_string()])
         .stdout(Stdio::null())
         .stderr(Stdio::null())
         .spawn()
-        .expect("Failed to start iperf3 server")
 }
 
 // Функция для запуска iperf теста
-pub fn run_iperf_test(server_ip: &str, iperf_port: u16, is_download: bool) -> Option<(f64, f64)> {
+pub fn run_iperf_test(
+    server_ip: &str,
+    iperf_port: u16,
+    is_download: bool,
+    options: &ThroughputOptions,
+) -> Option<ThroughputStats> {
     if !check_iperf_installed() {
         eprintln!("Error: iperf3 is not installed or not in PATH");
         return None;
@@ -29,17 +35,48 @@ pub fn run_iperf_test(server_ip: &str, iperf_port: u16, is_download: bool) -> Op
 
     // Создаем строку один раз, чтобы избежать временных значений
     let port_str = iperf_port.to_string();
+    let bitrate_str = format!("{}M", options.udp_bitrate_mbps);
+    let payload_str = options.udp_payload_size.to_string();
+    let duration_str = options.duration_secs.to_string();
+    let omit_str = options.omit_secs.to_string();
+    let parallel_str = options.parallel_streams.to_string();
+    let window_str = options.window_bytes.map(|bytes| bytes.to_string());
 
-    let mut args = vec!["-c", server_ip, "-p", &port_str, "-J", "-t", "5", "-i", "5"];
+    let mut args = vec![
+        "-c", server_ip, "-p", &port_str, "-J", "-t", &duration_str, "-i", &duration_str,
+    ];
+
+    if options.protocol == TestProtocol::Udp {
+        args.extend(["-u", "-b", &bitrate_str, "-l", &payload_str]);
+    }
+
+    if options.omit_secs > 0 {
+        args.extend(["-O", &omit_str]);
+    }
+
+    if options.parallel_streams > 1 {
+        args.extend(["-P", &parallel_str]);
+    }
+
+    if let Some(window) = &window_str {
+        args.extend(["-w", window]);
+    }
+
+    if let Some(congestion) = &options.congestion {
+        args.extend(["-C", congestion]);
+    }
 
     if is_download {
         args.push("-R");
     }
 
-    let output = Command::new("iperf3")
-        .args(&args)
-        .output()
-        .expect("Failed to execute iperf3 command");
+    let output = match Command::new("iperf3").args(&args).output() {
+        Ok(output) => output,
+        Err(e) => {
+            eprintln!("Failed to execute iperf3: {}", e);
+            return None;
+        }
+    };
 
     if output.status.success() {
         parse_iperf_output(&output.stdout)
@@ -50,7 +87,7 @@ pub fn run_iperf_test(server_ip: &str, iperf_port: u16, is_download: bool) -> Op
 }
 
 // Функция для парсинга вывода iperf
-pub fn parse_iperf_output(output: &[u8]) -> Option<(f64, f64)> {
+pub fn parse_iperf_output(output: &[u8]) -> Option<ThroughputStats> {
     let json_str = String::from_utf8_lossy(output);
     let json: Value = match serde_json::from_str(&json_str) {
         Ok(v) => v,
@@ -60,16 +97,89 @@ pub fn parse_iperf_output(output: &[u8]) -> Option<(f64, f64)> {
         }
     };
 
-    // Извлечь значения скорости получения и отправки
-    let rcv_mbps = json["end"]["sum_received"]["bits_per_second"]
+    // При ошибке iperf3 пишет ее в поле "error", а секция "end" остается пустой
+    if let Some(error) = json["error"].as_str() {
+        eprintln!("iperf3 reported an error: {}", error);
+        return None;
+    }
+
+    let end = &json["end"];
+
+    // Извлечь значения скорости получения и отправки.
+    // Старые версии iperf3 в режиме UDP пишут только общую секцию "sum".
+    let rcv_mbps = end["sum_received"]["bits_per_second"]
         .as_f64()
+        .or_else(|| end["sum"]["bits_per_second"].as_f64())
         .unwrap_or(0.0)
         / 1_000_000.0;
 
-    let send_mbps = json["end"]["sum_sent"]["bits_per_second"]
+    let send_mbps = end["sum_sent"]["bits_per_second"]
         .as_f64()
+        .or_else(|| end["sum"]["bits_per_second"].as_f64())
         .unwrap_or(0.0)
         / 1_000_000.0;
 
-    Some((rcv_mbps, send_mbps))
+    // Джиттер и потери есть только в UDP режиме
+    let jitter_ms = end["sum"]["jitter_ms"].as_f64().unwrap_or(0.0);
+    let lost_packets = end["sum"]["lost_packets"].as_u64().unwrap_or(0);
+    let loss_percent = end["sum"]["lost_percent"].as_f64().unwrap_or(0.0);
+
+    // Повторные передачи есть только в TCP режиме, их считает отправитель
+    let retransmits = end["sum_sent"]["retransmits"].as_u64().unwrap_or(0);
+
+    let cpu = &end["cpu_utilization_percent"];
+    let host_cpu_percent = cpu["host_total"].as_f64().unwrap_or(0.0);
+    let remote_cpu_percent = cpu["remote_total"].as_f64().unwrap_or(0.0);
+
+    Some(ThroughputStats {
+        rcv_mbps,
+        send_mbps,
+        jitter_ms,
+        lost_packets,
+        loss_percent,
+        retransmits,
+        host_cpu_percent,
+        remote_cpu_percent,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-6, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn parses_tcp_output() {
+        let stats = parse_iperf_output(include_bytes!("fixtures/iperf3_tcp.json")).unwrap();
+        assert_close(stats.send_mbps, 916.0068458);
+        assert_close(stats.rcv_mbps, 911.5430624);
+        assert_eq!(stats.retransmits, 37);
+        assert_close(stats.jitter_ms, 0.0);
+        assert_eq!(stats.lost_packets, 0);
+        assert_close(stats.loss_percent, 0.0);
+        assert_close(stats.host_cpu_percent, 21.48);
+        assert_close(stats.remote_cpu_percent, 34.17);
+    }
+
+    #[test]
+    fn parses_udp_output() {
+        let stats = parse_iperf_output(include_bytes!("fixtures/iperf3_udp.json")).unwrap();
+        assert_close(stats.send_mbps, 499.9943694);
+        assert_close(stats.rcv_mbps, 492.2622441);
+        assert_close(stats.jitter_ms, 0.0412);
+        assert_eq!(stats.lost_packets, 3912);
+        assert_close(stats.loss_percent, 1.502211);
+        assert_eq!(stats.retransmits, 0);
+        assert_close(stats.host_cpu_percent, 48.91);
+        assert_close(stats.remote_cpu_percent, 17.05);
+    }
+
+    #[test]
+    fn rejects_output_with_an_error() {
+        assert!(parse_iperf_output(include_bytes!("fixtures/iperf3_error.json")).is_none());
+        assert!(parse_iperf_output(b"iperf3: error - unable to connect").is_none());
+    }
 }
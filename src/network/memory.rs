@@ -0,0 +1,162 @@
+use crate::data::models::{ThroughputEngine, ThroughputOptions, ThroughputStats};
+use crate::error::AppError;
+use crate::network::error::MtuError;
+use crate::network::messages::{ControlTransport, Message};
+use crate::network::mtu::MtuController;
+use crate::network::throughput::ThroughputTester;
+use std::collections::HashMap;
+use std::io;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+// Наименьший MTU, который принимает ядро для IPv4
+const MIN_LINK_MTU: u32 = 68;
+
+// Управляющее соединение в памяти процесса. Сообщения проходят через JSON,
+// как и по TCP, поэтому сериализация проверяется так же, как в сети.
+pub struct MemoryTransport {
+    sender: Sender<Vec<u8>>,
+    receiver: Receiver<Vec<u8>>,
+    timeout: Option<Duration>,
+}
+
+// Функция для создания пары связанных соединений: сервер и пир.
+// timeout ограничивает ожидание сообщения, как таймаут чтения TCP.
+pub fn memory_transport_pair(timeout: Option<Duration>) -> (MemoryTransport, MemoryTransport) {
+    let (server_sender, peer_receiver) = mpsc::channel();
+    let (peer_sender, server_receiver) = mpsc::channel();
+
+    (
+        MemoryTransport {
+            sender: server_sender,
+            receiver: server_receiver,
+            timeout,
+        },
+        MemoryTransport {
+            sender: peer_sender,
+            receiver: peer_receiver,
+            timeout,
+        },
+    )
+}
+
+impl ControlTransport for MemoryTransport {
+    fn send_frame(&mut self, message: &Message) -> io::Result<()> {
+        let frame = serde_json::to_vec(message)?;
+        self.sender
+            .send(frame)
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "other side disconnected"))
+    }
+
+    fn receive_frame(&mut self) -> io::Result<Message> {
+        let frame = match self.timeout {
+            Some(timeout) => self.receiver.recv_timeout(timeout).map_err(|e| match e {
+                RecvTimeoutError::Timeout => io::Error::from(io::ErrorKind::TimedOut),
+                RecvTimeoutError::Disconnected => io::Error::from(io::ErrorKind::UnexpectedEof),
+            })?,
+            None => self
+                .receiver
+                .recv()
+                .map_err(|_| io::Error::from(io::ErrorKind::UnexpectedEof))?,
+        };
+
+        Ok(serde_json::from_slice(&frame)?)
+    }
+}
+
+// Интерфейсы в памяти процесса. Копии разделяют одну таблицу MTU,
+// так что сервер и пир видят изменения друг друга.
+#[derive(Clone, Default)]
+pub struct MemoryLinks {
+    mtus: Arc<Mutex<HashMap<String, u32>>>,
+}
+
+impl MemoryLinks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Функция для добавления интерфейса с начальным MTU
+    pub fn add(&self, interface: &str, mtu: u32) {
+        self.table().insert(interface.to_string(), mtu);
+    }
+
+    // Функция для получения MTU интерфейса, если он существует
+    pub fn mtu(&self, interface: &str) -> Option<u32> {
+        self.table().get(interface).copied()
+    }
+
+    fn table(&self) -> MutexGuard<'_, HashMap<String, u32>> {
+        self.mtus.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl MtuController for MemoryLinks {
+    fn get_mtu(&self, interface: &str) -> Result<u32, MtuError> {
+        self.mtu(interface)
+            .ok_or_else(|| MtuError::InterfaceNotFound(interface.to_string()))
+    }
+
+    fn set_mtu(&self, interface: &str, mtu: u32) -> Result<(), MtuError> {
+        let mut table = self.table();
+        let current = table
+            .get_mut(interface)
+            .ok_or_else(|| MtuError::InterfaceNotFound(interface.to_string()))?;
+
+        if mtu < MIN_LINK_MTU {
+            return Err(MtuError::InvalidMtu {
+                interface: interface.to_string(),
+                mtu,
+            });
+        }
+
+        *current = mtu;
+        Ok(())
+    }
+}
+
+// Замер скорости без сети: результат вычисляет модель по текущим MTU
+// интерфейсов сервера и пира и направлению теста
+pub struct MemoryThroughput<F> {
+    links: MemoryLinks,
+    server_interface: String,
+    peer_interface: String,
+    model: F,
+}
+
+impl<F> MemoryThroughput<F>
+where
+    F: Fn(u32, u32, bool) -> Option<ThroughputStats>,
+{
+    pub fn new(links: MemoryLinks, server_interface: &str, peer_interface: &str, model: F) -> Self {
+        MemoryThroughput {
+            links,
+            server_interface: server_interface.to_string(),
+            peer_interface: peer_interface.to_string(),
+            model,
+        }
+    }
+}
+
+impl<F> ThroughputTester for MemoryThroughput<F>
+where
+    F: Fn(u32, u32, bool) -> Option<ThroughputStats>,
+{
+    fn check_engine(&self, _engine: ThroughputEngine) -> Result<(), AppError> {
+        Ok(())
+    }
+
+    fn run_test(
+        &self,
+        _engine: ThroughputEngine,
+        _server_ip: &str,
+        _port: u16,
+        is_download: bool,
+        _options: &ThroughputOptions,
+    ) -> Option<ThroughputStats> {
+        let server_mtu = self.links.mtu(&self.server_interface)?;
+        let peer_mtu = self.links.mtu(&self.peer_interface)?;
+        (self.model)(server_mtu, peer_mtu, is_download)
+    }
+}
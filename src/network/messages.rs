@@ -1,6 +1,19 @@
+use crate::data::models::{ServerEndpoints, SweepConfig};
+use crate::error::AppError;
 use serde::{Deserialize, Serialize};
 use std::io::{self, Read, Write};
 use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// Версия управляющего протокола, должна совпадать на обеих сторонах
+pub const PROTOCOL_VERSION: u32 = 1;
+
+// Сообщения могут отправляться из нескольких потоков (heartbeat, обработчик сигналов),
+// поэтому кадр целиком записывается под блокировкой
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum Message {
@@ -8,21 +21,78 @@ pub enum Message {
     PeerDone,
     Finish,
     MtuValue(u32),
+    SetMtu(u32),
+    // Пир установил этот MTU у себя и начинает замер пары
+    PeerMtu(u32),
+    // Пир начинает согласование параметров
+    Hello {
+        version: u32,
+    },
+    // Параметры перебора сервера и MTU его интерфейса на момент запуска
+    Config {
+        config: SweepConfig,
+        current_mtu: Option<u32>,
+        endpoints: ServerEndpoints,
+    },
+    // Пир принял параметры сервера; apply_best — по окончании перебора пир
+    // пришлет ApplyMtu с лучшим MTU сервера
+    ConfigAccepted {
+        apply_best: bool,
+    },
+    // Лучший MTU сервера, который нужно оставить на интерфейсе после перебора
+    ApplyMtu(u32),
+    // Сторона не может продолжать перебор из-за ошибки
+    Error {
+        reason: String,
+    },
+    // Сторона прерывает перебор (например, по Ctrl-C)
+    Abort,
+    // Периодический сигнал о том, что сторона жива
+    Heartbeat,
+    // Пары (server_mtu, client_mtu), уже измеренные в прерванном переборе
+    CompletedPairs(Vec<(u32, u32)>),
+}
+
+// Управляющее соединение между сервером и пиром.
+// Истечение таймаута чтения сообщается ошибкой WouldBlock или TimedOut,
+// некорректное сообщение — ошибкой InvalidData.
+pub trait ControlTransport {
+    // Функция для отправки одного сообщения целиком
+    fn send_frame(&mut self, message: &Message) -> io::Result<()>;
+
+    // Функция для получения следующего сообщения
+    fn receive_frame(&mut self) -> io::Result<Message>;
+}
+
+impl ControlTransport for TcpStream {
+    fn send_frame(&mut self, message: &Message) -> io::Result<()> {
+        try_send_message(self, message)
+    }
+
+    fn receive_frame(&mut self) -> io::Result<Message> {
+        receive_message(self)
+    }
 }
 
-// Функция для отправки сообщения
-pub fn send_message<T: Serialize>(stream: &mut TcpStream, message: T) {
-    let serialized = serde_json::to_string(&message).expect("Failed to serialize message");
+// Функция для отправки управляющего сообщения
+pub fn send_message<T: ControlTransport>(stream: &mut T, message: Message) -> Result<(), AppError> {
+    stream.send_frame(&message).map_err(|e| {
+        AppError::Connection(format!("Failed to send {:?}: {}", message, e))
+    })
+}
+
+// Функция для отправки произвольного сообщения с ошибкой ввода-вывода
+pub fn try_send_message<T: Serialize>(stream: &mut TcpStream, message: &T) -> io::Result<()> {
+    let serialized = serde_json::to_string(message)?;
     let len = serialized.len() as u32;
-    let len_bytes = len.to_be_bytes();
 
-    stream
-        .write_all(&len_bytes)
-        .expect("Failed to write message length");
-    stream
-        .write_all(serialized.as_bytes())
-        .expect("Failed to write message");
-    stream.flush().expect("Failed to flush stream");
+    let mut frame = Vec::with_capacity(4 + serialized.len());
+    frame.extend_from_slice(&len.to_be_bytes());
+    frame.extend_from_slice(serialized.as_bytes());
+
+    let _lock = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    stream.write_all(&frame)?;
+    stream.flush()
 }
 
 // Функция для получения сообщения
@@ -37,3 +107,116 @@ pub fn receive_message<T: for<'de> Deserialize<'de>>(stream: &mut TcpStream) ->
     let message: T = serde_json::from_slice(&buffer)?;
     Ok(message)
 }
+
+// Функция для получения управляющего сообщения от другой стороны.
+// Ошибки чтения, Error и Abort превращаются в описание ошибки.
+// Heartbeat пропускается, истечение таймаута чтения означает, что сторона не отвечает.
+pub fn receive_control_message<T: ControlTransport>(
+    stream: &mut T,
+    from: &str,
+) -> Result<Message, AppError> {
+    loop {
+        return match stream.receive_frame() {
+            Ok(Message::Heartbeat) => continue,
+            Ok(Message::Error { reason }) => Err(AppError::Remote {
+                side: from.to_string(),
+                reason,
+            }),
+            Ok(Message::Abort) => Err(AppError::Aborted(from.to_string())),
+            Ok(message) => Ok(message),
+            Err(e)
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut =>
+            {
+                Err(AppError::Connection(format!(
+                    "{} is not responding, control connection timed out",
+                    from
+                )))
+            }
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => Err(AppError::Protocol(format!(
+                "Malformed message from {}: {}",
+                from, e
+            ))),
+            Err(e) => Err(AppError::Connection(format!(
+                "Error receiving message from {}: {}",
+                from, e
+            ))),
+        };
+    }
+}
+
+// Функция для сообщения другой стороне о неожиданном сообщении.
+// Возвращает ошибку протокола для передачи вызывающему.
+pub fn unexpected_message<T: ControlTransport>(
+    stream: &mut T,
+    from: &str,
+    expected: &str,
+    message: &Message,
+) -> AppError {
+    let reason = format!(
+        "Unexpected message from {}, expected {}: {:?}",
+        from, expected, message
+    );
+    report_error(stream, &reason);
+    AppError::Protocol(reason)
+}
+
+// Функция для сообщения другой стороне о локальной ошибке.
+// Соединение может быть уже закрыто, поэтому ошибки отправки игнорируются.
+pub fn report_error<T: ControlTransport>(stream: &mut T, reason: &str) {
+    let _ = stream.send_frame(&Message::Error {
+        reason: reason.to_string(),
+    });
+}
+
+// Функция для настройки таймаутов чтения и записи управляющего соединения
+pub fn set_control_timeouts(stream: &TcpStream, timeout: Duration) -> io::Result<()> {
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))
+}
+
+// Фоновая отправка Heartbeat, пока значение не будет удалено
+pub struct Heartbeat {
+    stop_flag: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+// Функция для запуска периодической отправки Heartbeat по управляющему соединению
+pub fn start_heartbeat(stream: &TcpStream, interval: Duration) -> io::Result<Heartbeat> {
+    let mut stream = stream.try_clone()?;
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    let thread_stop_flag = Arc::clone(&stop_flag);
+
+    let handle = thread::spawn(move || {
+        loop {
+            // Ждем интервал, просыпаясь при остановке
+            let started = Instant::now();
+            while !thread_stop_flag.load(Ordering::SeqCst) && started.elapsed() < interval {
+                thread::park_timeout(interval.saturating_sub(started.elapsed()));
+            }
+            if thread_stop_flag.load(Ordering::SeqCst) {
+                break;
+            }
+
+            // Ошибку отправки обнаружит основной поток при чтении
+            if try_send_message(&mut stream, &Message::Heartbeat).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(Heartbeat {
+        stop_flag,
+        handle: Some(handle),
+    })
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        self.stop_flag.store(true, Ordering::SeqCst);
+
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
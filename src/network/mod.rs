@@ -1,3 +1,9 @@
+pub mod error;
 pub mod iperf;
+pub mod memory;
 pub mod mtu;
-pub mod messages;
\ No newline at end of file
+pub mod messages;
+pub mod native;
+pub mod netlink;
+pub mod simulation;
+pub mod throughput;
@@ -1,33 +1,91 @@
-use std::process::Command;
 use std::thread;
-use std::time::Duration;
-use std::net::TcpStream;
-use crate::network::messages::{Message, receive_message};
+use std::time::{Duration, Instant};
+use crate::error::AppError;
+use crate::network::error::MtuError;
+use crate::network::messages::{
+    ControlTransport, Message, receive_control_message, unexpected_message,
+};
+use crate::network::netlink::{get_link_state, set_link_mtu};
+
+// Сколько ждать, пока интерфейс применит новый MTU
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(5);
+// Интервал опроса состояния интерфейса
+const SETTLE_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+// Управление MTU сетевых интерфейсов
+pub trait MtuController {
+    // Функция для получения текущего MTU интерфейса
+    fn get_mtu(&self, interface: &str) -> Result<u32, MtuError>;
+
+    // Функция для установки MTU на интерфейсе
+    fn set_mtu(&self, interface: &str, mtu: u32) -> Result<(), MtuError>;
+}
+
+// MTU интерфейсов системы, изменяется через netlink
+pub struct SystemMtu;
+
+impl MtuController for SystemMtu {
+    fn get_mtu(&self, interface: &str) -> Result<u32, MtuError> {
+        get_mtu(interface)
+    }
+
+    fn set_mtu(&self, interface: &str, mtu: u32) -> Result<(), MtuError> {
+        set_mtu(interface, mtu)
+    }
+}
 
 // Функция для установки MTU на интерфейсе
-pub fn set_mtu(interface: &str, mtu: u32) {
+pub fn set_mtu(interface: &str, mtu: u32) -> Result<(), MtuError> {
     println!("Setting MTU {} on interface {}", mtu, interface);
-    let output = Command::new("ip")
-        .args(&["link", "set", interface, "mtu", &mtu.to_string()])
-        .output()
-        .expect("Failed to execute ip command");
-
-    if !output.status.success() {
-        eprintln!(
-            "Warning: Failed to set MTU: {}",
-            String::from_utf8_lossy(&output.stderr)
-        );
+
+    // Запоминаем состояние линка, чтобы дождаться его восстановления
+    let before = get_link_state(interface)?;
+    set_link_mtu(interface, mtu)?;
+
+    wait_for_link(interface, mtu, before.operstate)
+}
+
+// Функция для ожидания применения MTU: опрашиваем интерфейс, пока ядро
+// не вернет новое значение и линк не вернется в прежнее состояние
+fn wait_for_link(interface: &str, mtu: u32, operstate: u8) -> Result<(), MtuError> {
+    let start = Instant::now();
+
+    loop {
+        let state = get_link_state(interface)?;
+        if state.mtu == mtu && state.operstate == operstate {
+            return Ok(());
+        }
+
+        if start.elapsed() >= SETTLE_TIMEOUT {
+            if state.mtu != mtu {
+                return Err(MtuError::NotApplied {
+                    interface: interface.to_string(),
+                    expected: mtu,
+                    actual: state.mtu,
+                });
+            }
+
+            eprintln!(
+                "Warning: Interface {} did not return to operstate {} after MTU change",
+                interface, operstate
+            );
+            return Ok(());
+        }
+
+        thread::sleep(SETTLE_POLL_INTERVAL);
     }
+}
 
-    // Небольшая пауза для применения изменений
-    thread::sleep(Duration::from_millis(500));
+// Функция для получения текущего MTU интерфейса
+pub fn get_mtu(interface: &str) -> Result<u32, MtuError> {
+    Ok(get_link_state(interface)?.mtu)
 }
 
 // Функция для получения удаленного MTU
-pub fn get_remote_mtu(stream: &mut TcpStream, _interface: &str) -> Result<u32, String> {
-    match receive_message::<Message>(stream) {
-        Ok(Message::MtuValue(mtu)) => Ok(mtu),
-        Ok(_) => Err("Unexpected message type from server".to_string()),
-        Err(e) => Err(format!("Failed to receive MTU from server: {}", e)),
+pub fn get_remote_mtu<T: ControlTransport>(stream: &mut T) -> Result<u32, AppError> {
+    match receive_control_message(stream, "Server")? {
+        Message::MtuValue(mtu) => Ok(mtu),
+        other => Err(unexpected_message(stream, "server", "MtuValue", &other)),
     }
 }
+
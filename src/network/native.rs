@@ -0,0 +1,687 @@
+use crate::data::models::{TestProtocol, ThroughputOptions, ThroughputStats};
+use crate::network::messages::{receive_message, try_send_message};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+use std::mem;
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::os::fd::AsRawFd;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// Размер одного блока данных при передаче по TCP
+const CHUNK_SIZE: usize = 128 * 1024;
+// Заголовок UDP пакета: порядковый номер и время отправки
+const UDP_HEADER_SIZE: usize = 16;
+// Номер пакета, которым клиент сообщает серверу свой UDP адрес
+const UDP_HELLO_SEQ: u64 = u64::MAX;
+// Сколько ждать новых UDP пакетов после окончания теста
+const UDP_DRAIN_TIMEOUT: Duration = Duration::from_millis(250);
+// Сколько ждать hello пакет от клиента
+const UDP_HELLO_TIMEOUT: Duration = Duration::from_secs(2);
+// Сколько ждать установки TCP соединения и запроса теста
+const TCP_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+// Запас сверх длительности теста для чтения и записи по TCP: если MTU
+// приводит к потере всех пакетов, тест должен завершиться ошибкой, а не зависнуть
+const TCP_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+// Направление передачи относительно клиента
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+enum TransferDirection {
+    Upload,
+    Download,
+}
+
+// Запрос клиента на проведение теста
+#[derive(Serialize, Deserialize, Debug)]
+struct TestRequest {
+    direction: TransferDirection,
+    options: ThroughputOptions,
+}
+
+// Отчет одной из сторон о переданных или полученных данных
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct TransferReport {
+    bytes: u64,
+    elapsed_secs: f64,
+    packets: u64,
+    jitter_ms: f64,
+    lost_packets: u64,
+    retransmits: u64,
+    cpu_percent: f64,
+}
+
+impl TransferReport {
+    fn mbps(&self) -> f64 {
+        if self.elapsed_secs <= 0.0 {
+            return 0.0;
+        }
+        self.bytes as f64 * 8.0 / self.elapsed_secs / 1_000_000.0
+    }
+}
+
+// Встроенный сервер для замера пропускной способности
+pub struct NativeServer {
+    port: u16,
+    stop_flag: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+// Функция для запуска встроенного сервера
+pub fn start_native_server(port: u16) -> io::Result<NativeServer> {
+    let listener = TcpListener::bind(SocketAddr::from(([0, 0, 0, 0], port)))?;
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    let thread_stop_flag = Arc::clone(&stop_flag);
+
+    let handle = thread::spawn(move || {
+        for stream in listener.incoming() {
+            if thread_stop_flag.load(Ordering::SeqCst) {
+                break;
+            }
+
+            // Каждое соединение обслуживается в своем потоке, чтобы работали параллельные потоки
+            match stream {
+                Ok(stream) => {
+                    thread::spawn(move || {
+                        if let Err(e) = handle_connection(stream) {
+                            eprintln!("Native throughput test failed: {}", e);
+                        }
+                    });
+                }
+                Err(e) => eprintln!("Failed to accept throughput connection: {}", e),
+            }
+        }
+    });
+
+    Ok(NativeServer {
+        port,
+        stop_flag,
+        handle: Some(handle),
+    })
+}
+
+impl NativeServer {
+    // Функция для остановки сервера
+    pub fn stop(mut self) {
+        self.stop_flag.store(true, Ordering::SeqCst);
+
+        // Разблокируем accept, подключившись к самому себе
+        let _ = TcpStream::connect(SocketAddr::from(([127, 0, 0, 1], self.port)));
+
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+// Функция для обработки одного теста на стороне сервера
+fn handle_connection(mut stream: TcpStream) -> io::Result<()> {
+    set_stream_timeout(&stream, TCP_CONNECT_TIMEOUT)?;
+    let request: TestRequest = receive_message(&mut stream).map_err(expired)?;
+    let options = &request.options;
+    set_stream_timeout(&stream, stream_timeout(options))?;
+
+    match (options.protocol, request.direction) {
+        (TestProtocol::Tcp, direction) => {
+            apply_socket_options(&stream, options, true)?;
+            match direction {
+                TransferDirection::Upload => tcp_receive_side(&mut stream, options),
+                TransferDirection::Download => tcp_send_side(&mut stream, options),
+            }
+            .map_err(expired)?;
+        }
+        (TestProtocol::Udp, direction) => {
+            // Данные идут по отдельному UDP сокету, порт сообщаем клиенту
+            let socket = UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], 0)))?;
+            apply_socket_options(&socket, options, false)?;
+            try_send_message(&mut stream, &socket.local_addr()?.port())?;
+
+            match direction {
+                TransferDirection::Upload => {
+                    udp_receive_side(&mut stream, &socket, &request.options)
+                }
+                TransferDirection::Download => wait_for_hello(&socket)
+                    .and_then(|_| udp_send_side(&mut stream, &socket, &request.options)),
+            }
+            .map_err(expired)?;
+        }
+    };
+
+    Ok(())
+}
+
+// Функция для запуска встроенного теста.
+// Каждый из параллельных потоков использует отдельное соединение, как -P в iperf3.
+pub fn run_native_test(
+    server_ip: &str,
+    port: u16,
+    is_download: bool,
+    options: &ThroughputOptions,
+) -> Option<ThroughputStats> {
+    let direction = if is_download {
+        TransferDirection::Download
+    } else {
+        TransferDirection::Upload
+    };
+
+    let handles: Vec<_> = (0..options.parallel_streams.max(1))
+        .map(|_| {
+            let server_ip = server_ip.to_string();
+            let options = options.clone();
+            thread::spawn(move || run_stream(&server_ip, port, direction, &options))
+        })
+        .collect();
+
+    let mut reports = Vec::new();
+    let mut failed = false;
+    for handle in handles {
+        match handle.join() {
+            Ok(Ok(report)) => reports.push(report),
+            Ok(Err(e)) => {
+                eprintln!("Native throughput test failed: {}", e);
+                failed = true;
+            }
+            Err(_) => {
+                eprintln!("Native throughput test failed: stream thread panicked");
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        return None;
+    }
+    Some(combine_reports(&reports, direction))
+}
+
+// Функция для проведения теста по одному соединению.
+// Возвращает пару (отчет получателя, отчет отправителя).
+fn run_stream(
+    server_ip: &str,
+    port: u16,
+    direction: TransferDirection,
+    options: &ThroughputOptions,
+) -> io::Result<(TransferReport, TransferReport)> {
+    let mut stream = connect(server_ip, port)?;
+    set_stream_timeout(&stream, stream_timeout(options))?;
+    let request = TestRequest {
+        direction,
+        options: options.clone(),
+    };
+    try_send_message(&mut stream, &request).map_err(expired)?;
+
+    let result = match (options.protocol, direction) {
+        (TestProtocol::Tcp, direction) => {
+            // В отличие от iperf3 буферы задаются уже после установки соединения
+            apply_socket_options(&stream, options, true)?;
+            match direction {
+                TransferDirection::Upload => tcp_send_side(&mut stream, options),
+                TransferDirection::Download => tcp_receive_side(&mut stream, options),
+            }
+        }
+        (TestProtocol::Udp, direction) => {
+            let udp_port: u16 = receive_message(&mut stream)?;
+            let socket = UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], 0)))?;
+            apply_socket_options(&socket, options, false)?;
+            socket.connect((server_ip, udp_port))?;
+            send_hello(&socket)?;
+
+            match direction {
+                TransferDirection::Upload => udp_send_side(&mut stream, &socket, options),
+                TransferDirection::Download => udp_receive_side(&mut stream, &socket, options),
+            }
+        }
+    };
+    result.map_err(expired)
+}
+
+// Функция для подключения к серверу с ограничением времени установки соединения
+fn connect(server_ip: &str, port: u16) -> io::Result<TcpStream> {
+    let mut last_error = None;
+    for addr in (server_ip, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, TCP_CONNECT_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(expired(e)),
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("No address found for {}", server_ip),
+        )
+    }))
+}
+
+// Наибольшее время ожидания одной операции чтения или записи по TCP
+fn stream_timeout(options: &ThroughputOptions) -> Duration {
+    let (_, total) = test_timing(options);
+    total + TCP_GRACE_PERIOD
+}
+
+fn set_stream_timeout(stream: &TcpStream, timeout: Duration) -> io::Result<()> {
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))
+}
+
+// Функция для приведения истекшего таймаута сокета к одной ошибке:
+// по таймауту read возвращает WouldBlock, а connect — TimedOut
+fn expired(e: io::Error) -> io::Error {
+    match e.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {
+            io::Error::new(io::ErrorKind::TimedOut, "Timed out waiting for the other side")
+        }
+        _ => e,
+    }
+}
+
+// Функция для сведения отчетов параллельных потоков: скорости, потери, повторные
+// передачи и загрузка CPU суммируются, джиттер усредняется
+fn combine_reports(
+    reports: &[(TransferReport, TransferReport)],
+    direction: TransferDirection,
+) -> ThroughputStats {
+    let rcv_mbps = reports.iter().map(|(received, _)| received.mbps()).sum();
+    let send_mbps = reports.iter().map(|(_, sent)| sent.mbps()).sum();
+    let lost_packets = reports.iter().map(|(received, _)| received.lost_packets).sum();
+    let sent_packets: u64 = reports.iter().map(|(_, sent)| sent.packets).sum();
+    let retransmits = reports.iter().map(|(_, sent)| sent.retransmits).sum();
+    let sender_cpu: f64 = reports.iter().map(|(_, sent)| sent.cpu_percent).sum();
+    let receiver_cpu: f64 = reports.iter().map(|(received, _)| received.cpu_percent).sum();
+    let jitter_ms = if reports.is_empty() {
+        0.0
+    } else {
+        reports.iter().map(|(received, _)| received.jitter_ms).sum::<f64>() / reports.len() as f64
+    };
+
+    let loss_percent = if sent_packets > 0 {
+        lost_packets as f64 / sent_packets as f64 * 100.0
+    } else {
+        0.0
+    };
+
+    // Клиент отправляет при upload и принимает при download
+    let (host_cpu_percent, remote_cpu_percent) = match direction {
+        TransferDirection::Upload => (sender_cpu, receiver_cpu),
+        TransferDirection::Download => (receiver_cpu, sender_cpu),
+    };
+
+    ThroughputStats {
+        rcv_mbps,
+        send_mbps,
+        jitter_ms,
+        lost_packets,
+        loss_percent,
+        retransmits,
+        host_cpu_percent,
+        remote_cpu_percent,
+    }
+}
+
+// Замер загрузки CPU текущим потоком за время теста
+struct CpuMeter {
+    started: Instant,
+    cpu_started: Duration,
+}
+
+impl CpuMeter {
+    fn start() -> Self {
+        CpuMeter {
+            started: Instant::now(),
+            cpu_started: thread_cpu_time(),
+        }
+    }
+
+    fn percent(&self) -> f64 {
+        let wall = self.started.elapsed().as_secs_f64();
+        if wall <= 0.0 {
+            return 0.0;
+        }
+        thread_cpu_time().saturating_sub(self.cpu_started).as_secs_f64() / wall * 100.0
+    }
+}
+
+// Функция для получения времени CPU (user + system), затраченного текущим потоком
+fn thread_cpu_time() -> Duration {
+    // SAFETY: rusage состоит из целых чисел, нулевое значение корректно
+    let mut usage: libc::rusage = unsafe { mem::zeroed() };
+    // SAFETY: передаем указатель на корректно инициализированную структуру
+    if unsafe { libc::getrusage(libc::RUSAGE_THREAD, &mut usage) } < 0 {
+        return Duration::ZERO;
+    }
+
+    let to_duration = |time: libc::timeval| {
+        Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+    };
+    to_duration(usage.ru_utime) + to_duration(usage.ru_stime)
+}
+
+// Функция для получения числа повторных передач TCP по данным ядра
+fn tcp_retransmits(stream: &TcpStream) -> io::Result<u64> {
+    // SAFETY: tcp_info состоит из целых чисел, нулевое значение корректно
+    let mut info: libc::tcp_info = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<libc::tcp_info>() as libc::socklen_t;
+
+    // SAFETY: передаем указатель на структуру и ее размер
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_INFO,
+            &mut info as *mut libc::tcp_info as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(info.tcpi_total_retrans as u64)
+}
+
+// Функция для установки размера буферов сокета и алгоритма управления перегрузкой TCP
+fn apply_socket_options(
+    socket: &impl AsRawFd,
+    options: &ThroughputOptions,
+    is_tcp: bool,
+) -> io::Result<()> {
+    if let Some(window) = options.window_bytes {
+        let size = libc::c_int::try_from(window).unwrap_or(libc::c_int::MAX);
+        for option in [libc::SO_SNDBUF, libc::SO_RCVBUF] {
+            set_socket_option(socket, libc::SOL_SOCKET, option, &size.to_ne_bytes())?;
+        }
+    }
+
+    if is_tcp && let Some(congestion) = &options.congestion {
+        set_socket_option(
+            socket,
+            libc::IPPROTO_TCP,
+            libc::TCP_CONGESTION,
+            congestion.as_bytes(),
+        )
+        .map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("Failed to set congestion control {}: {}", congestion, e),
+            )
+        })?;
+    }
+
+    Ok(())
+}
+
+fn set_socket_option(
+    socket: &impl AsRawFd,
+    level: libc::c_int,
+    option: libc::c_int,
+    value: &[u8],
+) -> io::Result<()> {
+    // SAFETY: передаем указатель на буфер и его точный размер
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            option,
+            value.as_ptr() as *const libc::c_void,
+            value.len() as libc::socklen_t,
+        )
+    };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// Функция для получения длительности пропускаемого начала теста и всего теста
+fn test_timing(options: &ThroughputOptions) -> (Duration, Duration) {
+    let omit = Duration::from_secs(options.omit_secs);
+    (omit, omit + Duration::from_secs(options.duration_secs))
+}
+
+// Передающая сторона TCP: отправляет данные, затем обменивается отчетами.
+// Возвращает пару (отчет получателя, отчет отправителя).
+fn tcp_send_side(
+    stream: &mut TcpStream,
+    options: &ThroughputOptions,
+) -> io::Result<(TransferReport, TransferReport)> {
+    let buffer = vec![0u8; CHUNK_SIZE];
+    let chunk_len = (CHUNK_SIZE as u32).to_be_bytes();
+    let (omit, total) = test_timing(options);
+    let mut bytes = 0u64;
+    let cpu = CpuMeter::start();
+
+    let start = Instant::now();
+    while start.elapsed() < total {
+        stream.write_all(&chunk_len)?;
+        stream.write_all(&buffer)?;
+
+        // Данные, отправленные в пропускаемом начале, не учитываются
+        if start.elapsed() >= omit {
+            bytes += CHUNK_SIZE as u64;
+        }
+    }
+
+    // Нулевая длина блока означает конец данных
+    stream.write_all(&0u32.to_be_bytes())?;
+    stream.flush()?;
+
+    let sent = TransferReport {
+        bytes,
+        elapsed_secs: start.elapsed().saturating_sub(omit).as_secs_f64(),
+        retransmits: tcp_retransmits(stream)?,
+        cpu_percent: cpu.percent(),
+        ..Default::default()
+    };
+    try_send_message(stream, &sent)?;
+
+    let received: TransferReport = receive_message(stream)?;
+    let _ = stream.shutdown(Shutdown::Both);
+
+    Ok((received, sent))
+}
+
+// Принимающая сторона TCP: считает полученные данные, затем обменивается отчетами.
+// Возвращает пару (отчет получателя, отчет отправителя).
+fn tcp_receive_side(
+    stream: &mut TcpStream,
+    options: &ThroughputOptions,
+) -> io::Result<(TransferReport, TransferReport)> {
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let (omit, _) = test_timing(options);
+    let mut bytes = 0u64;
+    let cpu = CpuMeter::start();
+
+    let start = Instant::now();
+    loop {
+        let mut len_bytes = [0u8; 4];
+        stream.read_exact(&mut len_bytes)?;
+        let len = u32::from_be_bytes(len_bytes) as usize;
+
+        if len == 0 {
+            break;
+        }
+        if len > CHUNK_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Chunk size {} exceeds limit {}", len, CHUNK_SIZE),
+            ));
+        }
+
+        stream.read_exact(&mut buffer[..len])?;
+        if start.elapsed() >= omit {
+            bytes += len as u64;
+        }
+    }
+
+    let received = TransferReport {
+        bytes,
+        elapsed_secs: start.elapsed().saturating_sub(omit).as_secs_f64(),
+        cpu_percent: cpu.percent(),
+        ..Default::default()
+    };
+
+    let sent: TransferReport = receive_message(stream)?;
+    try_send_message(stream, &received)?;
+
+    Ok((received, sent))
+}
+
+// Функция для отправки hello пакетов, по которым сервер узнает адрес клиента
+fn send_hello(socket: &UdpSocket) -> io::Result<()> {
+    let mut packet = [0u8; UDP_HEADER_SIZE];
+    packet[..8].copy_from_slice(&UDP_HELLO_SEQ.to_be_bytes());
+
+    // Отправляем несколько раз на случай потери
+    for _ in 0..3 {
+        socket.send(&packet)?;
+        thread::sleep(Duration::from_millis(20));
+    }
+    Ok(())
+}
+
+// Функция для ожидания hello пакета и привязки сокета к адресу клиента
+fn wait_for_hello(socket: &UdpSocket) -> io::Result<()> {
+    let mut packet = [0u8; UDP_HEADER_SIZE];
+    socket.set_read_timeout(Some(UDP_HELLO_TIMEOUT))?;
+
+    loop {
+        let (len, addr) = socket.recv_from(&mut packet)?;
+        if len >= 8 && u64::from_be_bytes(packet[..8].try_into().unwrap()) == UDP_HELLO_SEQ {
+            return socket.connect(addr);
+        }
+    }
+}
+
+// Передающая сторона UDP: отправляет пакеты с заданной скоростью.
+// Возвращает пару (отчет получателя, отчет отправителя).
+fn udp_send_side(
+    stream: &mut TcpStream,
+    socket: &UdpSocket,
+    options: &ThroughputOptions,
+) -> io::Result<(TransferReport, TransferReport)> {
+    let mut packet = vec![0u8; options.udp_payload_size.max(UDP_HEADER_SIZE)];
+    let bytes_per_sec = options.udp_bitrate_mbps * 1_000_000.0 / 8.0;
+    let (omit, total) = test_timing(options);
+    let mut sent_bytes = 0u64;
+    let mut bytes = 0u64;
+    let mut packets = 0u64;
+    let mut seq = 0u64;
+    let cpu = CpuMeter::start();
+
+    let start = Instant::now();
+    while start.elapsed() < total {
+        // Держим заданную скорость: отправляем, пока не догоним расписание
+        let target_bytes = bytes_per_sec * start.elapsed().as_secs_f64();
+        if (sent_bytes as f64) >= target_bytes {
+            thread::sleep(Duration::from_micros(200));
+            continue;
+        }
+
+        let sent_ns = start.elapsed().as_nanos() as u64;
+        packet[..8].copy_from_slice(&seq.to_be_bytes());
+        packet[8..UDP_HEADER_SIZE].copy_from_slice(&sent_ns.to_be_bytes());
+
+        match socket.send(&packet) {
+            Ok(_) => {}
+            // Ошибки отправки отдельных пакетов учитываются как потери
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {}
+            Err(e) => return Err(e),
+        }
+        seq += 1;
+        sent_bytes += packet.len() as u64;
+
+        // Пакеты пропускаемого начала не учитываются ни здесь, ни у получателя
+        if Duration::from_nanos(sent_ns) >= omit {
+            packets += 1;
+            bytes += packet.len() as u64;
+        }
+    }
+
+    let sent = TransferReport {
+        bytes,
+        elapsed_secs: start.elapsed().saturating_sub(omit).as_secs_f64(),
+        packets,
+        cpu_percent: cpu.percent(),
+        ..Default::default()
+    };
+    try_send_message(stream, &sent)?;
+
+    let received: TransferReport = receive_message(stream)?;
+
+    Ok((received, sent))
+}
+
+// Принимающая сторона UDP: считает пакеты, потери и джиттер (RFC 3550).
+// Возвращает пару (отчет получателя, отчет отправителя).
+fn udp_receive_side(
+    stream: &mut TcpStream,
+    socket: &UdpSocket,
+    options: &ThroughputOptions,
+) -> io::Result<(TransferReport, TransferReport)> {
+    let mut packet = vec![0u8; options.udp_payload_size.max(UDP_HEADER_SIZE)];
+    let mut bytes = 0u64;
+    let mut packets = 0u64;
+    let mut jitter_secs = 0.0;
+    let mut last_transit: Option<f64> = None;
+    let mut last_packet_at = Duration::ZERO;
+    let (omit, total) = test_timing(options);
+    let cpu = CpuMeter::start();
+
+    socket.set_read_timeout(Some(UDP_DRAIN_TIMEOUT))?;
+
+    let start = Instant::now();
+    loop {
+        match socket.recv(&mut packet) {
+            Ok(len) if len >= UDP_HEADER_SIZE => {
+                let seq = u64::from_be_bytes(packet[..8].try_into().unwrap());
+                if seq == UDP_HELLO_SEQ {
+                    continue;
+                }
+
+                let received_at = start.elapsed();
+                let sent_ns = u64::from_be_bytes(packet[8..UDP_HEADER_SIZE].try_into().unwrap());
+                if Duration::from_nanos(sent_ns) < omit {
+                    continue;
+                }
+
+                // Часы сторон не синхронизированы, но для джиттера важна только разница
+                let transit = received_at.as_secs_f64() - sent_ns as f64 / 1_000_000_000.0;
+                if let Some(previous) = last_transit {
+                    let delta: f64 = transit - previous;
+                    jitter_secs += (delta.abs() - jitter_secs) / 16.0;
+                }
+                last_transit = Some(transit);
+
+                packets += 1;
+                bytes += len as u64;
+                last_packet_at = received_at;
+            }
+            Ok(_) => {}
+            Err(e)
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut =>
+            {
+                if start.elapsed() >= total {
+                    break;
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    let sent: TransferReport = receive_message(stream)?;
+
+    let received = TransferReport {
+        bytes,
+        elapsed_secs: last_packet_at.saturating_sub(omit).as_secs_f64(),
+        packets,
+        jitter_ms: jitter_secs * 1000.0,
+        lost_packets: sent.packets.saturating_sub(packets),
+        cpu_percent: cpu.percent(),
+        ..Default::default()
+    };
+    try_send_message(stream, &received)?;
+
+    Ok((received, sent))
+}
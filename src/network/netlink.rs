@@ -0,0 +1,288 @@
+use crate::network::error::MtuError;
+use std::ffi::CString;
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+// Размеры заголовков nlmsghdr и ifinfomsg
+const NLMSG_HEADER_LEN: usize = 16;
+const IFINFOMSG_LEN: usize = 16;
+const RTATTR_HEADER_LEN: usize = 4;
+const RECEIVE_BUFFER_SIZE: usize = 32 * 1024;
+
+// Атрибуты rtattr ответа: (тип, данные)
+type Attributes = Vec<(u16, Vec<u8>)>;
+
+// Состояние сетевого интерфейса по данным ядра
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkState {
+    pub mtu: u32,
+    pub operstate: u8,
+}
+
+// Сокет NETLINK_ROUTE для запросов к ядру
+struct NetlinkSocket {
+    fd: OwnedFd,
+    seq: u32,
+}
+
+impl NetlinkSocket {
+    fn open() -> io::Result<Self> {
+        // SAFETY: обычный системный вызов, результат проверяется ниже
+        let raw_fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                libc::NETLINK_ROUTE,
+            )
+        };
+        if raw_fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: дескриптор только что получен и больше никому не принадлежит
+        let fd = unsafe { OwnedFd::from_raw_fd(raw_fd) };
+
+        // SAFETY: sockaddr_nl состоит из целых чисел, нулевое значение корректно
+        let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+
+        // SAFETY: передаем указатель на корректно инициализированную структуру и ее размер
+        let result = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(NetlinkSocket { fd, seq: 0 })
+    }
+
+    // Функция для отправки запроса по интерфейсу и получения ответа.
+    // Возвращает атрибуты RTM_NEWLINK или пустой список для подтверждения.
+    fn request(
+        &mut self,
+        msg_type: u16,
+        flags: u16,
+        index: i32,
+        attrs: &[(u16, &[u8])],
+    ) -> io::Result<Attributes> {
+        self.seq += 1;
+        let message = build_link_message(msg_type, flags, self.seq, index, attrs);
+
+        // SAFETY: буфер валиден на протяжении вызова
+        let sent = unsafe {
+            libc::send(
+                self.fd.as_raw_fd(),
+                message.as_ptr() as *const libc::c_void,
+                message.len(),
+                0,
+            )
+        };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut buffer = vec![0u8; RECEIVE_BUFFER_SIZE];
+        loop {
+            // SAFETY: буфер валиден и имеет указанный размер
+            let received = unsafe {
+                libc::recv(
+                    self.fd.as_raw_fd(),
+                    buffer.as_mut_ptr() as *mut libc::c_void,
+                    buffer.len(),
+                    0,
+                )
+            };
+            if received < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            if let Some(reply) = parse_reply(&buffer[..received as usize], self.seq)? {
+                return Ok(reply);
+            }
+        }
+    }
+}
+
+// Функция для сборки сообщения nlmsghdr + ifinfomsg + rtattr
+fn build_link_message(
+    msg_type: u16,
+    flags: u16,
+    seq: u32,
+    index: i32,
+    attrs: &[(u16, &[u8])],
+) -> Vec<u8> {
+    let mut message = Vec::with_capacity(64);
+
+    // nlmsghdr, длина заполняется в конце
+    message.extend_from_slice(&0u32.to_ne_bytes());
+    message.extend_from_slice(&msg_type.to_ne_bytes());
+    message.extend_from_slice(&flags.to_ne_bytes());
+    message.extend_from_slice(&seq.to_ne_bytes());
+    message.extend_from_slice(&0u32.to_ne_bytes());
+
+    // ifinfomsg
+    message.push(libc::AF_UNSPEC as u8);
+    message.push(0);
+    message.extend_from_slice(&0u16.to_ne_bytes());
+    message.extend_from_slice(&index.to_ne_bytes());
+    message.extend_from_slice(&0u32.to_ne_bytes());
+    message.extend_from_slice(&0u32.to_ne_bytes());
+
+    for (attr_type, data) in attrs {
+        let len = (RTATTR_HEADER_LEN + data.len()) as u16;
+        message.extend_from_slice(&len.to_ne_bytes());
+        message.extend_from_slice(&attr_type.to_ne_bytes());
+        message.extend_from_slice(data);
+        message.resize(align(message.len()), 0);
+    }
+
+    let total_len = message.len() as u32;
+    message[..4].copy_from_slice(&total_len.to_ne_bytes());
+    message
+}
+
+// Функция для разбора ответа ядра. None, если ответ относится к другому запросу.
+fn parse_reply(buffer: &[u8], seq: u32) -> io::Result<Option<Attributes>> {
+    let mut offset = 0;
+
+    while offset + NLMSG_HEADER_LEN <= buffer.len() {
+        let len = read_u32(buffer, offset) as usize;
+        let msg_type = read_u16(buffer, offset + 4);
+        let msg_seq = read_u32(buffer, offset + 8);
+
+        if len < NLMSG_HEADER_LEN || offset + len > buffer.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Truncated netlink message",
+            ));
+        }
+
+        if msg_seq == seq {
+            let payload = &buffer[offset + NLMSG_HEADER_LEN..offset + len];
+
+            if msg_type == libc::NLMSG_ERROR as u16 {
+                // Ноль в поле error означает подтверждение
+                let errno = -(read_u32(payload, 0) as i32);
+                if errno == 0 {
+                    return Ok(Some(Vec::new()));
+                }
+                return Err(io::Error::from_raw_os_error(errno));
+            }
+
+            if msg_type == libc::RTM_NEWLINK && payload.len() >= IFINFOMSG_LEN {
+                return Ok(Some(parse_attributes(&payload[IFINFOMSG_LEN..])));
+            }
+        }
+
+        offset += align(len);
+    }
+
+    Ok(None)
+}
+
+// Функция для разбора списка rtattr
+fn parse_attributes(mut data: &[u8]) -> Attributes {
+    let mut attrs = Vec::new();
+
+    while data.len() >= RTATTR_HEADER_LEN {
+        let len = read_u16(data, 0) as usize;
+        let attr_type = read_u16(data, 2);
+
+        if len < RTATTR_HEADER_LEN || len > data.len() {
+            break;
+        }
+
+        attrs.push((attr_type, data[RTATTR_HEADER_LEN..len].to_vec()));
+        data = &data[align(len).min(data.len())..];
+    }
+
+    attrs
+}
+
+fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+fn read_u16(buffer: &[u8], offset: usize) -> u16 {
+    u16::from_ne_bytes([buffer[offset], buffer[offset + 1]])
+}
+
+fn read_u32(buffer: &[u8], offset: usize) -> u32 {
+    u32::from_ne_bytes(buffer[offset..offset + 4].try_into().unwrap())
+}
+
+// Функция для получения индекса интерфейса по имени
+fn interface_index(interface: &str) -> Result<i32, MtuError> {
+    let name =
+        CString::new(interface).map_err(|_| MtuError::InterfaceNotFound(interface.to_string()))?;
+
+    // SAFETY: передаем корректную C-строку
+    let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    if index == 0 {
+        return Err(MtuError::InterfaceNotFound(interface.to_string()));
+    }
+
+    Ok(index as i32)
+}
+
+// Функция для преобразования ошибок ядра в типизированные
+fn map_error(err: io::Error, interface: &str, mtu: Option<u32>) -> MtuError {
+    match (err.raw_os_error(), mtu) {
+        (Some(libc::EPERM) | Some(libc::EACCES), _) => {
+            MtuError::PermissionDenied(interface.to_string())
+        }
+        (Some(libc::ENODEV), _) => MtuError::InterfaceNotFound(interface.to_string()),
+        (Some(libc::EINVAL), Some(mtu)) => MtuError::InvalidMtu {
+            interface: interface.to_string(),
+            mtu,
+        },
+        _ => MtuError::Netlink(err),
+    }
+}
+
+// Функция для чтения состояния интерфейса через RTM_GETLINK
+pub fn get_link_state(interface: &str) -> Result<LinkState, MtuError> {
+    let index = interface_index(interface)?;
+    let mut socket = NetlinkSocket::open()?;
+
+    let attrs = socket
+        .request(libc::RTM_GETLINK, libc::NLM_F_REQUEST as u16, index, &[])
+        .map_err(|e| map_error(e, interface, None))?;
+
+    let mut state = LinkState {
+        mtu: 0,
+        operstate: 0,
+    };
+    for (attr_type, data) in attrs {
+        if attr_type == libc::IFLA_MTU && data.len() >= 4 {
+            state.mtu = read_u32(&data, 0);
+        } else if attr_type == libc::IFLA_OPERSTATE && !data.is_empty() {
+            state.operstate = data[0];
+        }
+    }
+
+    Ok(state)
+}
+
+// Функция для установки MTU через RTM_SETLINK с подтверждением от ядра
+pub fn set_link_mtu(interface: &str, mtu: u32) -> Result<(), MtuError> {
+    let index = interface_index(interface)?;
+    let mut socket = NetlinkSocket::open()?;
+
+    let mtu_bytes = mtu.to_ne_bytes();
+    socket
+        .request(
+            libc::RTM_SETLINK,
+            (libc::NLM_F_REQUEST | libc::NLM_F_ACK) as u16,
+            index,
+            &[(libc::IFLA_MTU, &mtu_bytes)],
+        )
+        .map_err(|e| map_error(e, interface, Some(mtu)))?;
+
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use crate::data::models::{SimulationModel, TestProtocol, ThroughputOptions, ThroughputStats};
+use std::f64::consts::PI;
+use std::sync::Mutex;
+
+// Накладные расходы Ethernet на один пакет: преамбула, заголовок, FCS и межкадровый интервал
+const ETHERNET_FRAMING: u32 = 38;
+// Заголовок IPv4, добавляемый каждому дополнительному фрагменту
+const FRAGMENT_HEADER: u32 = 20;
+// Заголовки IPv4 и TCP/UDP внутри туннеля
+const TCP_HEADERS: u32 = 40;
+const UDP_HEADERS: u32 = 28;
+// Базовый джиттер и его рост при фрагментации
+const BASE_JITTER_MS: f64 = 0.05;
+const FRAGMENTED_JITTER_FACTOR: f64 = 4.0;
+// Доля пакетов, передаваемых повторно при фрагментации, от потерянной скорости
+const FRAGMENTED_RETRANSMIT_RATIO: f64 = 0.02;
+// Пакетов в секунду на один процент загрузки CPU
+const PACKETS_PER_CPU_PERCENT: f64 = 5000.0;
+
+// Синтетическая сеть: скорость определяется размером пакетов внутри туннеля,
+// накладными расходами инкапсуляции и фрагментацией внешних пакетов
+pub struct SimulatedNetwork {
+    model: SimulationModel,
+    rng: Mutex<u64>,
+}
+
+impl SimulatedNetwork {
+    pub fn new(model: SimulationModel) -> Self {
+        let rng = Mutex::new(model.seed);
+        SimulatedNetwork { model, rng }
+    }
+
+    // Функция для расчета результата теста при заданных MTU сервера и пира
+    pub fn measure(
+        &self,
+        server_mtu: u32,
+        peer_mtu: u32,
+        is_download: bool,
+        options: &ThroughputOptions,
+    ) -> ThroughputStats {
+        let (sender_mtu, receiver_mtu) = if is_download {
+            (server_mtu, peer_mtu)
+        } else {
+            (peer_mtu, server_mtu)
+        };
+
+        // Размер пакета внутри туннеля: TCP ограничен MSS обеих сторон,
+        // UDP дейтаграмма больше MTU отправителя фрагментируется уже в туннеле
+        let (packet, headers, inner_fragmented) = match options.protocol {
+            TestProtocol::Tcp => (sender_mtu.min(receiver_mtu), TCP_HEADERS, false),
+            TestProtocol::Udp => {
+                let payload = u32::try_from(options.udp_payload_size).unwrap_or(u32::MAX);
+                let datagram = payload.saturating_add(UDP_HEADERS);
+                (datagram.min(sender_mtu), UDP_HEADERS, datagram > sender_mtu)
+            }
+        };
+        let packet = packet.max(headers + 1);
+
+        // Внешний пакет больше MTU сети под туннелем делится на два фрагмента
+        let outer = packet + self.model.overhead;
+        let fragmented = inner_fragmented || outer > self.model.underlay_mtu;
+        let wire_bytes = if fragmented {
+            outer + FRAGMENT_HEADER + 2 * ETHERNET_FRAMING
+        } else {
+            outer + ETHERNET_FRAMING
+        };
+        let payload = packet - headers;
+
+        let penalty = if fragmented {
+            self.model.fragmentation_penalty
+        } else {
+            0.0
+        };
+        let efficiency = payload as f64 / wire_bytes as f64;
+        let capacity = self.model.bandwidth_mbps * efficiency * (1.0 - penalty);
+        let capacity = capacity * self.noise_factor();
+
+        let (rcv_mbps, send_mbps, lost_packets, loss_percent, retransmits) = match options.protocol {
+            TestProtocol::Tcp => {
+                let retransmit_share = penalty * FRAGMENTED_RETRANSMIT_RATIO;
+                let packets = capacity * 1_000_000.0 / 8.0 / payload as f64
+                    * options.duration_secs as f64;
+                let retransmits = (packets * retransmit_share).round() as u64;
+                (capacity, capacity * (1.0 + retransmit_share), 0, 0.0, retransmits)
+            }
+            TestProtocol::Udp => {
+                let offered = options.udp_bitrate_mbps;
+                let delivered = offered.min(capacity);
+                let loss = if offered > 0.0 { 1.0 - delivered / offered } else { 0.0 };
+                let datagrams = offered * 1_000_000.0 / 8.0 / options.udp_payload_size.max(1) as f64
+                    * options.duration_secs as f64;
+                let lost = (datagrams * loss).round() as u64;
+                (delivered, offered, lost, loss * 100.0, 0)
+            }
+        };
+
+        let jitter_ms = match options.protocol {
+            TestProtocol::Tcp => 0.0,
+            TestProtocol::Udp if fragmented => {
+                BASE_JITTER_MS * FRAGMENTED_JITTER_FACTOR * self.noise_factor()
+            }
+            TestProtocol::Udp => BASE_JITTER_MS * self.noise_factor(),
+        };
+
+        // Загрузка CPU растет с числом пакетов в секунду
+        let packets_per_sec = send_mbps * 1_000_000.0 / 8.0 / packet as f64;
+        let wire_packets_per_sec = if fragmented {
+            packets_per_sec * 2.0
+        } else {
+            packets_per_sec
+        };
+        let cpu_percent = (wire_packets_per_sec / PACKETS_PER_CPU_PERCENT).min(100.0);
+
+        ThroughputStats {
+            rcv_mbps,
+            send_mbps,
+            jitter_ms,
+            lost_packets,
+            loss_percent,
+            retransmits,
+            host_cpu_percent: cpu_percent * self.noise_factor(),
+            remote_cpu_percent: cpu_percent * self.noise_factor(),
+        }
+    }
+
+    // Функция для получения случайного множителя 1 ± noise (нормальное распределение)
+    fn noise_factor(&self) -> f64 {
+        if self.model.noise <= 0.0 {
+            return 1.0;
+        }
+
+        // Преобразование Бокса — Мюллера
+        let u1 = self.next_uniform().max(f64::MIN_POSITIVE);
+        let u2 = self.next_uniform();
+        let normal = (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos();
+
+        (1.0 + self.model.noise * normal).max(0.0)
+    }
+
+    // Функция для получения равномерно распределенного числа в [0, 1) (SplitMix64)
+    fn next_uniform(&self) -> f64 {
+        let mut state = self.rng.lock().unwrap_or_else(|e| e.into_inner());
+        *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z = *state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
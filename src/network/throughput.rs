@@ -0,0 +1,104 @@
+use crate::data::models::{ThroughputEngine, ThroughputOptions, ThroughputStats};
+use crate::error::AppError;
+use crate::network::iperf::{check_iperf_installed, run_iperf_test, start_iperf_server};
+use crate::network::native::{NativeServer, run_native_test, start_native_server};
+use std::process::Child;
+
+// Запущенный сервер замера скорости выбранного движка
+pub enum ThroughputServer {
+    Iperf3(Child),
+    Native(NativeServer),
+}
+
+// Замер скорости на стороне пира
+pub trait ThroughputTester {
+    // Функция для проверки доступности движка
+    fn check_engine(&self, engine: ThroughputEngine) -> Result<(), AppError>;
+
+    // Функция для запуска одного теста скорости, None означает неудачный замер
+    fn run_test(
+        &self,
+        engine: ThroughputEngine,
+        server_ip: &str,
+        port: u16,
+        is_download: bool,
+        options: &ThroughputOptions,
+    ) -> Option<ThroughputStats>;
+}
+
+// Замер скорости через сеть системы выбранным движком
+pub struct SystemThroughput;
+
+impl ThroughputTester for SystemThroughput {
+    fn check_engine(&self, engine: ThroughputEngine) -> Result<(), AppError> {
+        check_engine_available(engine)
+    }
+
+    fn run_test(
+        &self,
+        engine: ThroughputEngine,
+        server_ip: &str,
+        port: u16,
+        is_download: bool,
+        options: &ThroughputOptions,
+    ) -> Option<ThroughputStats> {
+        run_throughput_test(engine, server_ip, port, is_download, options)
+    }
+}
+
+// Функция для проверки доступности движка
+pub fn check_engine_available(engine: ThroughputEngine) -> Result<(), AppError> {
+    match engine {
+        ThroughputEngine::Iperf3 if !check_iperf_installed() => Err(AppError::Engine(
+            "iperf3 is not installed or not in PATH".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Функция для запуска сервера замера скорости
+pub fn start_throughput_server(
+    engine: ThroughputEngine,
+    port: u16,
+) -> Result<ThroughputServer, AppError> {
+    let server = match engine {
+        ThroughputEngine::Iperf3 => start_iperf_server(port).map(ThroughputServer::Iperf3),
+        ThroughputEngine::Native => start_native_server(port).map(ThroughputServer::Native),
+    };
+
+    server.map_err(|e| {
+        AppError::Engine(format!(
+            "Failed to start {:?} throughput server on port {}: {}",
+            engine, port, e
+        ))
+    })
+}
+
+impl ThroughputServer {
+    // Функция для остановки сервера
+    pub fn stop(self) {
+        match self {
+            ThroughputServer::Iperf3(mut process) => {
+                if let Err(e) = process.kill() {
+                    eprintln!("Failed to kill iperf3 server process: {}", e);
+                }
+                let _ = process.wait();
+            }
+            ThroughputServer::Native(server) => server.stop(),
+        }
+    }
+}
+
+// Функция для запуска теста скорости выбранным движком
+pub fn run_throughput_test(
+    engine: ThroughputEngine,
+    server_ip: &str,
+    port: u16,
+    is_download: bool,
+    options: &ThroughputOptions,
+) -> Option<ThroughputStats> {
+    match engine {
+        ThroughputEngine::Iperf3 => run_iperf_test(server_ip, port, is_download, options),
+        ThroughputEngine::Native => run_native_test(server_ip, port, is_download, options),
+    }
+}
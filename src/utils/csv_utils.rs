@@ -1,43 +1,211 @@
-use crate::data::models::MtuTestResult;
+use crate::data::models::{MetricSummary, MtuTestResult};
+use crate::error::AppError;
 use csv::Writer;
-use std::fs::File;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader};
+
+// Заголовок CSV файла с результатами
+const CSV_HEADER: [&str; 18] = [
+    "server_mtu",
+    "client_mtu",
+    "upload_rcv_mbps",
+    "upload_send_mbps",
+    "download_rcv_mbps",
+    "download_send_mbps",
+    "upload_jitter_ms",
+    "upload_lost_packets",
+    "upload_loss_percent",
+    "download_jitter_ms",
+    "download_lost_packets",
+    "download_loss_percent",
+    "upload_retransmits",
+    "download_retransmits",
+    "upload_peer_cpu_percent",
+    "upload_server_cpu_percent",
+    "download_peer_cpu_percent",
+    "download_server_cpu_percent",
+];
+
+// Метрики, для которых сохраняется статистика повторов, и ее колонки
+const SUMMARY_METRICS: [&str; 14] = [
+    "upload_rcv_mbps",
+    "upload_send_mbps",
+    "download_rcv_mbps",
+    "download_send_mbps",
+    "upload_jitter_ms",
+    "upload_loss_percent",
+    "download_jitter_ms",
+    "download_loss_percent",
+    "upload_retransmits",
+    "download_retransmits",
+    "upload_peer_cpu_percent",
+    "upload_server_cpu_percent",
+    "download_peer_cpu_percent",
+    "download_server_cpu_percent",
+];
+const SUMMARY_STATISTICS: [&str; 5] = ["median", "stddev", "min", "max", "ci95"];
+
+// Функция для получения полного заголовка CSV файла
+fn csv_header() -> Vec<String> {
+    let mut header: Vec<String> = CSV_HEADER.iter().map(|c| c.to_string()).collect();
+    header.push("repeats".to_string());
+
+    for metric in SUMMARY_METRICS {
+        for statistic in SUMMARY_STATISTICS {
+            header.push(format!("{}_{}", metric, statistic));
+        }
+    }
+
+    header
+}
+
+// Статистика повторов в порядке SUMMARY_METRICS
+fn summaries(result: &MtuTestResult) -> [&MetricSummary; 14] {
+    [
+        &result.upload_rcv_stats,
+        &result.upload_send_stats,
+        &result.download_rcv_stats,
+        &result.download_send_stats,
+        &result.upload_jitter_stats,
+        &result.upload_loss_stats,
+        &result.download_jitter_stats,
+        &result.download_loss_stats,
+        &result.upload_retransmits_stats,
+        &result.download_retransmits_stats,
+        &result.upload_peer_cpu_stats,
+        &result.upload_server_cpu_stats,
+        &result.download_peer_cpu_stats,
+        &result.download_server_cpu_stats,
+    ]
+}
 
 // Функция для создания CSV файла
-pub fn create_csv_file(filename: &str) -> Writer<File> {
-    let file = File::create(filename).expect("Failed to create CSV file");
+pub fn create_csv_file(filename: &str) -> Result<Writer<File>, AppError> {
+    let file = File::create(filename)
+        .map_err(|e| AppError::Output(format!("Failed to create CSV file {}: {}", filename, e)))?;
     let mut writer = Writer::from_writer(file);
 
     // Записываем заголовок
     writer
-        .write_record(&[
-            "server_mtu",
-            "client_mtu",
-            "upload_rcv_mbps",
-            "upload_send_mbps",
-            "download_rcv_mbps",
-            "download_send_mbps",
-        ])
-        .expect("Failed to write CSV header");
-
-    let file = writer.into_inner().expect("Failed to get file from writer");
-    let writer = Writer::from_writer(file);
+        .write_record(csv_header())
+        .and_then(|_| writer.flush().map_err(csv::Error::from))
+        .map_err(|e| AppError::Output(format!("Failed to write CSV header: {}", e)))?;
 
-    writer
+    Ok(writer)
+}
+
+// Функция для открытия существующего CSV файла на дозапись.
+// Заголовок файла должен совпадать с текущим, иначе строки разойдутся по колонкам.
+pub fn append_csv_file(filename: &str) -> Result<Writer<File>, AppError> {
+    let file = File::open(filename)
+        .map_err(|e| AppError::Output(format!("Failed to open CSV file {}: {}", filename, e)))?;
+
+    let mut header = String::new();
+    BufReader::new(file)
+        .read_line(&mut header)
+        .map_err(|e| AppError::Output(format!("Failed to read CSV file {}: {}", filename, e)))?;
+
+    if header.trim_end() != csv_header().join(",") {
+        return Err(AppError::Config(format!(
+            "CSV file {} has a different set of columns and cannot be resumed",
+            filename
+        )));
+    }
+
+    let file = OpenOptions::new()
+        .append(true)
+        .open(filename)
+        .map_err(|e| {
+            AppError::Output(format!(
+                "Failed to open CSV file {} for appending: {}",
+                filename, e
+            ))
+        })?;
+    Ok(Writer::from_writer(file))
 }
 
 // Функция для сохранения результата в CSV
-pub fn save_result_to_csv(writer: &mut Writer<File>, result: &MtuTestResult) {
+pub fn save_result_to_csv(
+    writer: &mut Writer<File>,
+    result: &MtuTestResult,
+) -> Result<(), AppError> {
+    let mut record = vec![
+        result.server_mtu.to_string(),
+        result.client_mtu.to_string(),
+        result.upload_rcv_mbps.to_string(),
+        result.upload_send_mbps.to_string(),
+        result.download_rcv_mbps.to_string(),
+        result.download_send_mbps.to_string(),
+        result.upload_jitter_ms.to_string(),
+        result.upload_lost_packets.to_string(),
+        result.upload_loss_percent.to_string(),
+        result.download_jitter_ms.to_string(),
+        result.download_lost_packets.to_string(),
+        result.download_loss_percent.to_string(),
+        result.upload_retransmits.to_string(),
+        result.download_retransmits.to_string(),
+        result.upload_peer_cpu_percent.to_string(),
+        result.upload_server_cpu_percent.to_string(),
+        result.download_peer_cpu_percent.to_string(),
+        result.download_server_cpu_percent.to_string(),
+        result.repeats.to_string(),
+    ];
+
+    for summary in summaries(result) {
+        record.extend([
+            summary.median.to_string(),
+            summary.stddev.to_string(),
+            summary.min.to_string(),
+            summary.max.to_string(),
+            summary.ci95.to_string(),
+        ]);
+    }
+
     writer
-        .write_record(&[
-            result.server_mtu.to_string(),
-            result.client_mtu.to_string(),
-            result.upload_rcv_mbps.to_string(),
-            result.upload_send_mbps.to_string(),
-            result.download_rcv_mbps.to_string(),
-            result.download_send_mbps.to_string(),
-        ])
-        .expect("Failed to write CSV record");
+        .write_record(&record)
+        .map_err(|e| AppError::Output(format!("Failed to write CSV record: {}", e)))?;
 
     // Flush после каждой записи
-    writer.flush().expect("Failed to flush CSV writer");
-}
\ No newline at end of file
+    writer
+        .flush()
+        .map_err(|e| AppError::Output(format!("Failed to flush CSV writer: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn temp_csv(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("csv-utils-{}-{}.csv", name, std::process::id()))
+    }
+
+    #[test]
+    fn append_rejects_a_different_header() {
+        let path = temp_csv("mismatch");
+        let filename = path.to_str().unwrap();
+        let contents = "server_mtu,client_mtu,upload_mbps\n1420,1420,900.0\n";
+        fs::write(&path, contents).unwrap();
+
+        let result = append_csv_file(filename);
+        let unchanged = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let error = result.expect_err("a different header must not be resumed");
+        assert!(matches!(error, AppError::Config(_)));
+        assert!(error.to_string().contains("different set of columns"));
+        assert_eq!(unchanged, contents);
+    }
+
+    #[test]
+    fn append_accepts_the_current_header() {
+        let path = temp_csv("match");
+        let filename = path.to_str().unwrap();
+        drop(create_csv_file(filename).unwrap());
+
+        let result = append_csv_file(filename);
+        fs::remove_file(&path).unwrap();
+        assert!(result.is_ok());
+    }
+}
@@ -0,0 +1,31 @@
+use crate::data::models::RunMetadata;
+use crate::error::AppError;
+use std::fs;
+
+// Функция для получения пути к файлу метаданных для CSV файла
+pub fn metadata_path(csv_file: &str) -> String {
+    format!("{}.meta.json", csv_file)
+}
+
+// Функция для сохранения метаданных запуска
+pub fn save_metadata(csv_file: &str, metadata: &RunMetadata) -> Result<(), AppError> {
+    let path = metadata_path(csv_file);
+    let json = serde_json::to_string_pretty(metadata)
+        .map_err(|e| AppError::Output(format!("Failed to serialize metadata: {}", e)))?;
+
+    fs::write(&path, json)
+        .map_err(|e| AppError::Output(format!("Failed to write metadata file {}: {}", path, e)))
+}
+
+// Функция для загрузки метаданных запуска, если они есть
+pub fn load_metadata(csv_file: &str) -> Option<RunMetadata> {
+    let content = fs::read_to_string(metadata_path(csv_file)).ok()?;
+
+    match serde_json::from_str(&content) {
+        Ok(metadata) => Some(metadata),
+        Err(e) => {
+            eprintln!("Warning: Ignoring invalid metadata file: {}", e);
+            None
+        }
+    }
+}
@@ -1 +1,4 @@
-pub mod csv_utils;
\ No newline at end of file
+pub mod csv_utils;
+pub mod metadata;
+pub mod stats;
+pub mod wg_quick;
@@ -0,0 +1,112 @@
+use crate::data::models::MetricSummary;
+
+// Двусторонние критические значения t-распределения для 95% при 1..=30 степенях свободы
+const T_CRITICAL_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+// Для большего числа степеней свободы используем нормальное распределение
+const Z_CRITICAL_95: f64 = 1.960;
+
+// Функция для расчета статистики по повторным замерам
+pub fn summarize(samples: &[f64]) -> MetricSummary {
+    if samples.is_empty() {
+        return MetricSummary::default();
+    }
+
+    let n = samples.len();
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(f64::total_cmp);
+
+    let mean = sorted.iter().sum::<f64>() / n as f64;
+    let median = if n.is_multiple_of(2) {
+        (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0
+    } else {
+        sorted[n / 2]
+    };
+
+    // Выборочное стандартное отклонение и доверительный интервал для n > 1
+    let (stddev, ci95) = if n > 1 {
+        let variance =
+            sorted.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+        let stddev = variance.sqrt();
+        let t = T_CRITICAL_95.get(n - 2).copied().unwrap_or(Z_CRITICAL_95);
+        (stddev, t * stddev / (n as f64).sqrt())
+    } else {
+        (0.0, 0.0)
+    };
+
+    MetricSummary {
+        mean,
+        median,
+        stddev,
+        min: sorted[0],
+        max: sorted[n - 1],
+        ci95,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn odd_count_takes_the_middle_sample() {
+        let summary = summarize(&[3.0, 1.0, 2.0]);
+        assert_close(summary.mean, 2.0);
+        assert_close(summary.median, 2.0);
+        assert_close(summary.stddev, 1.0);
+        assert_close(summary.min, 1.0);
+        assert_close(summary.max, 3.0);
+        // Two degrees of freedom
+        assert_close(summary.ci95, 4.303 / 3f64.sqrt());
+    }
+
+    #[test]
+    fn even_count_averages_the_two_middle_samples() {
+        let summary = summarize(&[4.0, 1.0, 10.0, 2.0]);
+        assert_close(summary.mean, 4.25);
+        assert_close(summary.median, 3.0);
+        assert_close(summary.min, 1.0);
+        assert_close(summary.max, 10.0);
+        let stddev = (48.75f64 / 3.0).sqrt();
+        assert_close(summary.stddev, stddev);
+        assert_close(summary.ci95, 3.182 * stddev / 2.0);
+    }
+
+    #[test]
+    fn single_sample_has_no_spread() {
+        let summary = summarize(&[7.5]);
+        assert_close(summary.mean, 7.5);
+        assert_close(summary.median, 7.5);
+        assert_close(summary.min, 7.5);
+        assert_close(summary.max, 7.5);
+        assert_close(summary.stddev, 0.0);
+        assert_close(summary.ci95, 0.0);
+
+        let empty = summarize(&[]);
+        assert_close(empty.mean, 0.0);
+        assert_close(empty.ci95, 0.0);
+    }
+
+    #[test]
+    fn ci_narrows_with_more_samples() {
+        // Same spread, the interval shrinks with t and with the square root of n
+        let few = summarize(&[9.0, 11.0, 9.0, 11.0]);
+        let many: Vec<f64> = (0..40).map(|i| if i % 2 == 0 { 9.0 } else { 11.0 }).collect();
+        let many = summarize(&many);
+        assert_close(few.ci95, 3.182 * few.stddev / 2.0);
+        assert_close(many.ci95, Z_CRITICAL_95 * many.stddev / 40f64.sqrt());
+        assert!(many.ci95 < few.ci95 / 3.0);
+
+        // 31 samples is the last row of the t table
+        let table_end: Vec<f64> = (0..31).map(|i| i as f64).collect();
+        let summary = summarize(&table_end);
+        assert_close(summary.ci95, 2.042 * summary.stddev / 31f64.sqrt());
+    }
+}
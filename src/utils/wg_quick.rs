@@ -0,0 +1,324 @@
+use crate::error::AppError;
+use std::fs::{self, OpenOptions, Permissions};
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+
+// Строк контекста вокруг изменения в diff
+const DIFF_CONTEXT: usize = 3;
+
+// Права исправленной конфигурации: в ней PrivateKey, читать ее может только владелец
+const CONFIG_FILE_MODE: u32 = 0o600;
+
+// Функция для установки MTU в секции [Interface] конфигурации wg-quick.
+// В существующих строках MTU заменяется только значение, если их нет —
+// строка добавляется после последнего параметра секции. Остальные строки,
+// включая комментарии и окончания строк, не меняются.
+pub fn set_interface_mtu(config: &str, mtu: u32) -> Result<String, AppError> {
+    let mut lines: Vec<String> = config.split_inclusive('\n').map(str::to_string).collect();
+
+    // Строка, после которой добавляется MTU: последний параметр [Interface]
+    let mut insert_after: Option<usize> = None;
+    let mut in_interface = false;
+    let mut replaced = false;
+    for (index, line) in lines.iter_mut().enumerate() {
+        // wg-quick отбрасывает все после # и пробелы по краям
+        let stripped = line.split('#').next().unwrap_or_default().trim();
+
+        if stripped.starts_with('[') {
+            in_interface = stripped == "[Interface]";
+            if in_interface && insert_after.is_none() {
+                insert_after = Some(index);
+            }
+            continue;
+        }
+        if !in_interface || stripped.is_empty() {
+            continue;
+        }
+
+        insert_after = Some(index);
+        let key = stripped.split('=').next().unwrap_or_default().trim();
+        if key.eq_ignore_ascii_case("mtu") {
+            *line = replace_value(line, mtu);
+            replaced = true;
+        }
+    }
+
+    let Some(insert_after) = insert_after else {
+        return Err(AppError::Config(
+            "No [Interface] section found in wg-quick config".to_string(),
+        ));
+    };
+
+    if !replaced {
+        let after = &mut lines[insert_after];
+        // Окончание строки как у предыдущей, у последней строки без него — как в файле
+        let crlf = if after.ends_with('\n') {
+            after.ends_with("\r\n")
+        } else {
+            config.contains("\r\n")
+        };
+        let ending = if crlf { "\r\n" } else { "\n" };
+        if !after.ends_with('\n') {
+            after.push_str(ending);
+        }
+        lines.insert(insert_after + 1, format!("MTU = {}{}", mtu, ending));
+    }
+
+    Ok(lines.concat())
+}
+
+// Функция для замены значения в строке "MTU = ...": меняется только само
+// значение, пробелы, комментарий и окончание строки остаются прежними
+fn replace_value(line: &str, mtu: u32) -> String {
+    let Some(equals) = line.find('=') else {
+        return line.to_string();
+    };
+    let (key, rest) = line.split_at(equals + 1);
+    let value_start = rest.len() - rest.trim_start_matches([' ', '\t']).len();
+    let value_end = rest[value_start..]
+        .find(|c: char| c.is_whitespace() || c == '#')
+        .map_or(rest.len(), |end| value_start + end);
+
+    format!(
+        "{}{}{}{}",
+        key,
+        &rest[..value_start],
+        mtu,
+        &rest[value_end..]
+    )
+}
+
+// Функция для применения MTU к файлу конфигурации: пишет исправленный файл
+// с тем же именем в output_dir либо, если output_dir не задан, печатает diff
+pub fn patch_config_file(path: &str, mtu: u32, output_dir: Option<&Path>) -> Result<(), AppError> {
+    let original = fs::read_to_string(path)
+        .map_err(|e| AppError::Output(format!("Failed to read wg-quick config {}: {}", path, e)))?;
+    let patched =
+        set_interface_mtu(&original, mtu).map_err(|e| e.context(format!("in {}", path)))?;
+
+    let Some(output_dir) = output_dir else {
+        if original == patched {
+            println!("{} already has MTU = {}", path, mtu);
+        } else {
+            print!("{}", unified_diff(path, &original, &patched));
+        }
+        return Ok(());
+    };
+
+    let file_name = Path::new(path)
+        .file_name()
+        .ok_or_else(|| AppError::Config(format!("Invalid wg-quick config path {}", path)))?;
+    let output = output_dir.join(file_name);
+    fs::create_dir_all(output_dir).map_err(|e| {
+        AppError::Output(format!(
+            "Failed to create directory {}: {}",
+            output_dir.display(),
+            e
+        ))
+    })?;
+    write_private_file(&output, &patched).map_err(|e| {
+        AppError::Output(format!(
+            "Failed to write wg-quick config {}: {}",
+            output.display(),
+            e
+        ))
+    })?;
+
+    println!("Wrote {} with MTU = {}", output.display(), mtu);
+    Ok(())
+}
+
+// Функция для записи файла, доступного только владельцу. Права ставятся до
+// записи содержимого, в том числе если файл уже существовал с более широкими.
+fn write_private_file(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(CONFIG_FILE_MODE)
+        .open(path)?;
+    file.set_permissions(Permissions::from_mode(CONFIG_FILE_MODE))?;
+    file.write_all(contents.as_bytes())
+}
+
+// Функция для построения построчного diff в формате unified.
+// Конфигурации небольшие, поэтому достаточно наибольшей общей подпоследовательности.
+pub fn unified_diff(path: &str, original: &str, patched: &str) -> String {
+    let old: Vec<&str> = original.lines().collect();
+    let new: Vec<&str> = patched.lines().collect();
+
+    // lcs[i][j] — длина общей подпоследовательности old[i..] и new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    // Последовательность строк: ' ' — без изменений, '-' — удалена, '+' — добавлена
+    let mut edits: Vec<(char, usize, usize)> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            edits.push((' ', i, j));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            edits.push(('-', i, j));
+            i += 1;
+        } else {
+            edits.push(('+', i, j));
+            j += 1;
+        }
+    }
+
+    let mut diff = String::new();
+    let changed: Vec<usize> = (0..edits.len()).filter(|&k| edits[k].0 != ' ').collect();
+    if changed.is_empty() {
+        return diff;
+    }
+    diff.push_str(&format!("--- {}\n+++ {}\n", path, path));
+
+    // Изменения, между которыми не больше двух контекстов, попадают в один блок
+    let mut start = 0;
+    while start < changed.len() {
+        let mut end = start;
+        while end + 1 < changed.len() && changed[end + 1] - changed[end] <= 2 * DIFF_CONTEXT {
+            end += 1;
+        }
+        let first = changed[start].saturating_sub(DIFF_CONTEXT);
+        let last = (changed[end] + DIFF_CONTEXT).min(edits.len() - 1);
+        let hunk = &edits[first..=last];
+
+        let old_count = hunk.iter().filter(|edit| edit.0 != '+').count();
+        let new_count = hunk.iter().filter(|edit| edit.0 != '-').count();
+        let (_, old_start, new_start) = hunk[0];
+        diff.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            hunk_start(old_start, old_count),
+            old_count,
+            hunk_start(new_start, new_count),
+            new_count
+        ));
+        for &(kind, i, j) in hunk {
+            let line = if kind == '+' { new[j] } else { old[i] };
+            diff.push_str(&format!("{}{}\n", kind, line));
+        }
+
+        start = end + 1;
+    }
+
+    diff
+}
+
+// Номер первой строки блока: с единицы, для пустого блока — строка перед ним
+fn hunk_start(index: usize, count: usize) -> usize {
+    if count == 0 { index } else { index + 1 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_only_the_value_of_an_existing_mtu_line() {
+        assert_eq!(replace_value("MTU = 1420\n", 1380), "MTU = 1380\n");
+        assert_eq!(replace_value("mtu=1420", 1380), "mtu=1380");
+        assert_eq!(
+            replace_value("Mtu\t=\t1420  # tuned\r\n", 1380),
+            "Mtu\t=\t1380  # tuned\r\n"
+        );
+        assert_eq!(replace_value("MTU =\n", 1380), "MTU =1380\n");
+    }
+
+    #[test]
+    fn keeps_case_and_spacing_of_the_mtu_key() {
+        let config = "[Interface]\nPrivateKey = abc\n  mTu=1500 # old\nAddress = 10.0.0.1/24\n";
+        let patched = set_interface_mtu(config, 1420).unwrap();
+        assert_eq!(
+            patched,
+            "[Interface]\nPrivateKey = abc\n  mTu=1420 # old\nAddress = 10.0.0.1/24\n"
+        );
+    }
+
+    #[test]
+    fn inserts_mtu_after_the_last_interface_line() {
+        let config =
+            "[Interface]\nPrivateKey = abc\nAddress = 10.0.0.1/24\n\n[Peer]\nPublicKey = def\n";
+        let patched = set_interface_mtu(config, 1420).unwrap();
+        assert_eq!(
+            patched,
+            "[Interface]\nPrivateKey = abc\nAddress = 10.0.0.1/24\nMTU = 1420\n\n\
+             [Peer]\nPublicKey = def\n"
+        );
+
+        // Line endings follow the file, a missing final newline is added
+        let patched = set_interface_mtu("[Interface]\r\nPrivateKey = abc", 1420).unwrap();
+        assert_eq!(patched, "[Interface]\r\nPrivateKey = abc\r\nMTU = 1420\r\n");
+    }
+
+    #[test]
+    fn only_the_interface_section_is_changed() {
+        // An MTU-like key in [Peer] is left alone, [Interface] may come last
+        let config =
+            "[Peer]\nPublicKey = def\nMTU = 1500\n\n[Interface]\nPrivateKey = abc\n# MTU = 1280\n";
+        let patched = set_interface_mtu(config, 1420).unwrap();
+        assert_eq!(
+            patched,
+            "[Peer]\nPublicKey = def\nMTU = 1500\n\n\
+             [Interface]\nPrivateKey = abc\nMTU = 1420\n# MTU = 1280\n"
+        );
+
+        let error = set_interface_mtu("[Peer]\nPublicKey = def\n", 1420).unwrap_err();
+        assert!(matches!(error, AppError::Config(_)));
+    }
+
+    #[test]
+    fn diff_shows_the_changed_line_with_context() {
+        let original =
+            "[Interface]\nPrivateKey = abc\nAddress = 10.0.0.1/24\n\n[Peer]\nPublicKey = def\n";
+        let patched = set_interface_mtu(original, 1420).unwrap();
+        assert_eq!(
+            unified_diff("wg0.conf", original, &patched),
+            "--- wg0.conf\n+++ wg0.conf\n@@ -1,6 +1,7 @@\n [Interface]\n PrivateKey = abc\n \
+             Address = 10.0.0.1/24\n+MTU = 1420\n \n [Peer]\n PublicKey = def\n"
+        );
+
+        let original = "[Interface]\nMTU = 1500\n";
+        let patched = set_interface_mtu(original, 1420).unwrap();
+        assert_eq!(
+            unified_diff("wg0.conf", original, &patched),
+            "--- wg0.conf\n+++ wg0.conf\n@@ -1,2 +1,2 @@\n [Interface]\n-MTU = 1500\n+MTU = 1420\n"
+        );
+
+        assert_eq!(unified_diff("wg0.conf", original, original), "");
+    }
+
+    #[test]
+    fn patched_file_is_readable_by_the_owner_only() {
+        let dir = std::env::temp_dir().join(format!("wg-quick-test-{}", std::process::id()));
+        let input = dir.join("input");
+        let output = dir.join("output");
+        fs::create_dir_all(&input).unwrap();
+        fs::create_dir_all(&output).unwrap();
+        let config = input.join("wg0.conf");
+        fs::write(&config, "[Interface]\nPrivateKey = abc\n").unwrap();
+        // A previous copy with wider permissions is tightened as well
+        fs::write(output.join("wg0.conf"), "").unwrap();
+        fs::set_permissions(output.join("wg0.conf"), Permissions::from_mode(0o644)).unwrap();
+
+        patch_config_file(config.to_str().unwrap(), 1420, Some(&output)).unwrap();
+
+        let written = output.join("wg0.conf");
+        let mode = fs::metadata(&written).unwrap().permissions().mode() & 0o777;
+        let contents = fs::read_to_string(&written).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(mode, CONFIG_FILE_MODE);
+        assert_eq!(contents, "[Interface]\nPrivateKey = abc\nMTU = 1420\n");
+    }
+}